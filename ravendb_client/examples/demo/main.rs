@@ -21,33 +21,17 @@ async fn main() -> anyhow::Result<()> {
 
 #[instrument(level = "info", name = "Running")]
 async fn run() -> anyhow::Result<()> {
-    // Get the url scheme from environment variable
-    let scheme: String = std::env::var("RAVEN_SCHEME").unwrap_or_else(|_| "http".to_string());
-    let proxy = std::env::var("RAVEN_PROXY").ok();
-
-    // Override dns settings to use local servers
+    // Override dns settings to use the local servers, each published on its own port
     let mut dns_overrides = HashMap::<String, String>::new();
-    dns_overrides.insert("raven1".to_string(), "127.0.0.1".to_string());
-    dns_overrides.insert("raven2".to_string(), "127.0.0.1".to_string());
-    dns_overrides.insert("raven3".to_string(), "127.0.0.1".to_string());
-
-    // Instantiate a new document store builder
-    let mut document_store = DocumentStoreBuilder::new();
-    if scheme == "https" {
-        tracing::info!("`RAVEN_SCHEME` set to 'https'. Using pem file.");
-        document_store = document_store
-            .set_client_certificate("ravendb-client_dev_cert.pem")
-            .set_urls(&["https://a.free.damccull.ravendb.cloud"])
-            .set_dns_overrides(dns_overrides);
-    } else {
-        tracing::warn!("`RAVEN_SCHEME` not set or set to 'http'. Connecting insecurly and without authentication.");
-        document_store = document_store
-            .set_urls(&["http://localhost:8080"])
-            .set_dns_overrides(dns_overrides);
-    }
-
-    if let Some(proxy) = proxy {
-        document_store = document_store.set_proxy_address(proxy.as_str());
+    dns_overrides.insert("raven1".to_string(), "127.0.0.1:8080".to_string());
+    dns_overrides.insert("raven2".to_string(), "127.0.0.1:8081".to_string());
+    dns_overrides.insert("raven3".to_string(), "127.0.0.1:8082".to_string());
+
+    // Instantiate a new document store builder from `RAVEN_URLS`, `RAVEN_CERT` and friends
+    let mut document_store = DocumentStoreBuilder::from_env()?.set_dns_overrides(dns_overrides);
+    if std::env::var("RAVEN_URLS").is_err() {
+        tracing::warn!("`RAVEN_URLS` not set. Connecting insecurly and without authentication.");
+        document_store = document_store.set_urls(&["http://localhost:8080"]);
     }
 
     // Actually build the document store
@@ -1,20 +1,22 @@
 use ravendb_client::{
-    raven_command::{RavenCommand, RavenCommandVariant},
+    raven_command::{GetClusterTopologyCommand, RavenCommand},
     ravendb_error::RavenDbError,
+    server_node::ServerNode,
 };
 use reqwest::{Client, Response};
 use url::Url;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let raven_command = RavenCommand {
-        base_server_url: Url::parse("https://a.free.damccull.ravendb.cloud/")?,
-        command: RavenCommandVariant::GetClusterTopology,
-    };
-    let request = raven_command.get_http_request()?;
+    let node = ServerNode::new(
+        Url::parse("https://a.free.damccull.ravendb.cloud/")?,
+        "MyDb".to_string(),
+    );
+    let request = GetClusterTopologyCommand.create_request(&node)?;
 
     let client = Client::new();
     let f = client.execute(request);
+    #[allow(clippy::redundant_async_block)]
     let r: Result<Response, reqwest::Error> = tokio::spawn(async move { f.await }).await.unwrap();
     dbg!(r?);
 
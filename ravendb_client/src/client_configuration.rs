@@ -0,0 +1,28 @@
+use serde::Deserialize;
+
+use crate::{LoadBalanceBehavior, ReadBalanceBehavior};
+
+/// The response of the server's `/configuration/client` endpoint.
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct GetClientConfigurationResult {
+    pub etag: i64,
+    pub configuration: Option<ClientConfiguration>,
+}
+
+/// Client settings an administrator can push from the server. When present and not disabled,
+/// these take precedence over the matching values in the store's
+/// [`DocumentConventions`](crate::DocumentConventions).
+#[derive(Clone, Debug, Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClientConfiguration {
+    #[serde(default)]
+    pub etag: i64,
+    #[serde(default)]
+    pub disabled: bool,
+    pub identity_parts_separator: Option<char>,
+    pub load_balance_behavior: Option<LoadBalanceBehavior>,
+    pub load_balancer_context_seed: Option<i32>,
+    pub max_number_of_requests_per_session: Option<i32>,
+    pub read_balance_behavior: Option<ReadBalanceBehavior>,
+}
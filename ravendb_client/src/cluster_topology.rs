@@ -1,10 +1,10 @@
 use std::collections::HashMap;
 
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Default)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", default)]
 pub struct ClusterTopologyInfo {
     #[serde(rename = "@metadata")]
     pub metadata: HashMap<String, String>, // TODO: Determine if needed or useful and delete if not
@@ -20,7 +20,7 @@ pub struct ClusterTopologyInfo {
     pub status: HashMap<String, NodeStatus>,
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Clone, Debug, Deserialize, Default, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ClusterTopology {
     pub topology_id: String,
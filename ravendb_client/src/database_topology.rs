@@ -1,5 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
+use serde::{Deserialize, Serialize};
+
 use crate::server_node::ServerNode;
 
 #[derive(Clone, Debug, Default)]
@@ -13,3 +15,32 @@ pub struct DatabaseTopology {
     /// Maintains a list of response times (in milliseconds) for each node in the topology
     pub node_response_speed_ms: HashMap<ServerNode, u32>,
 }
+
+/// The response of the server's `/topology` endpoint. This is also the format topologies are
+/// cached on disk in.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GetDatabaseTopologyResult {
+    pub nodes: Vec<ServerNode>,
+    pub etag: i64,
+}
+
+impl From<GetDatabaseTopologyResult> for DatabaseTopology {
+    fn from(result: GetDatabaseTopologyResult) -> Self {
+        Self {
+            // The server uses -1 for "no etag", which is the same as never having seen one.
+            etag: u64::try_from(result.etag).unwrap_or_default(),
+            nodes: result.nodes.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&DatabaseTopology> for GetDatabaseTopologyResult {
+    fn from(topology: &DatabaseTopology) -> Self {
+        Self {
+            nodes: topology.nodes.iter().cloned().collect(),
+            etag: i64::try_from(topology.etag).unwrap_or(i64::MAX),
+        }
+    }
+}
@@ -1,7 +1,50 @@
-#[derive(Clone, Debug)]
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::client_configuration::ClientConfiguration;
+
+/// Settings that shape how a [`DocumentStore`](crate::DocumentStore) and its sessions behave.
+///
+/// Conventions are moved into the store when it is built, and are frozen from then on: the
+/// store only hands out [read-only access](crate::DocumentStore::conventions) to them.
+///
+/// Deserializes from the convention names. Durations are given in seconds, and conventions left
+/// out keep their default. [`find_collection_name`](Self::find_collection_name) can only be set
+/// in code.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct DocumentConventions {
+    #[serde(deserialize_with = "deserialize_secs")]
+    cluster_topology_refresh_interval: Duration,
+    #[serde(deserialize_with = "deserialize_secs")]
+    database_topology_refresh_interval: Duration,
     disable_topology_updates: bool,
+    #[serde(skip)]
+    find_collection_name: CollectionNameFinder,
+    #[serde(deserialize_with = "deserialize_secs")]
+    first_broadcast_attempt_timeout: Duration,
+    http_compression_algorithm: HttpCompressionAlgorithm,
+    identity_parts_separator: char,
+    load_balance_behavior: LoadBalanceBehavior,
+    load_balancer_context_seed: i32,
+    max_http_cache_size: usize,
+    max_number_of_requests_per_session: usize,
+    read_balance_behavior: ReadBalanceBehavior,
+    #[serde(deserialize_with = "deserialize_optional_secs")]
+    request_timeout: Option<Duration>,
     send_application_identified: bool,
+    topology_cache_location: Option<PathBuf>,
+    #[serde(deserialize_with = "deserialize_secs")]
+    topology_refresh_jitter: Duration,
+    use_compression: bool,
+    #[serde(deserialize_with = "deserialize_secs")]
+    wait_for_indexes_after_save_changes_timeout: Duration,
 }
 
 //TODO: Remove this when default can no longer be derived
@@ -9,8 +52,24 @@ pub struct DocumentConventions {
 impl Default for DocumentConventions {
     fn default() -> Self {
         Self {
+            cluster_topology_refresh_interval: Duration::from_secs(60 * 5),
+            database_topology_refresh_interval: Duration::from_secs(60),
             disable_topology_updates: bool::default(),
-            send_application_identified: bool::default(),
+            find_collection_name: CollectionNameFinder::default(),
+            first_broadcast_attempt_timeout: Duration::from_secs(5),
+            http_compression_algorithm: HttpCompressionAlgorithm::default(),
+            identity_parts_separator: '/',
+            load_balance_behavior: LoadBalanceBehavior::default(),
+            load_balancer_context_seed: i32::default(),
+            max_http_cache_size: 128 * 1024 * 1024,
+            max_number_of_requests_per_session: 30,
+            read_balance_behavior: ReadBalanceBehavior::default(),
+            request_timeout: None,
+            send_application_identified: true,
+            topology_cache_location: None,
+            topology_refresh_jitter: Duration::from_secs(10),
+            use_compression: true,
+            wait_for_indexes_after_save_changes_timeout: Duration::from_secs(15),
         }
     }
 }
@@ -22,11 +81,427 @@ impl DocumentConventions {
             ..Default::default()
         }
     }
+
+    /// Sets how often the cluster topology is refreshed. Defaults to 5 minutes.
+    pub fn set_cluster_topology_refresh_interval(mut self, interval: Duration) -> Self {
+        self.cluster_topology_refresh_interval = interval;
+        self
+    }
+
+    /// Sets how often each database's topology is refreshed. Defaults to 1 minute.
+    pub fn set_database_topology_refresh_interval(mut self, interval: Duration) -> Self {
+        self.database_topology_refresh_interval = interval;
+        self
+    }
+
+    /// Sets the largest random delay added to every topology refresh, so that many clients
+    /// started together don't all hit the cluster at the same moment. Defaults to 10 seconds.
+    pub fn set_topology_refresh_jitter(mut self, jitter: Duration) -> Self {
+        self.topology_refresh_jitter = jitter;
+        self
+    }
+
+    /// Disables topology updates. Each `RequestExecutor` then only ever talks to the first url
+    /// of the store, which suits servers behind a load balancer or a Kubernetes service.
+    pub fn set_disable_topology_updates(mut self, disable: bool) -> Self {
+        self.disable_topology_updates = disable;
+        self
+    }
+
+    /// Sets how the collection of an entity is named from its type name, such as `Order`.
+    /// Defaults to the pluralized type name, `Orders`.
+    pub fn set_find_collection_name<F>(mut self, find_collection_name: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.find_collection_name = CollectionNameFinder(Arc::new(find_collection_name));
+        self
+    }
+
+    /// Sets how long the first attempt of a command sent to every node may take.
+    ///
+    /// Reserved for when commands can be broadcast to every node. Nothing uses it yet.
+    pub fn set_first_broadcast_attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.first_broadcast_attempt_timeout = timeout;
+        self
+    }
+
+    /// Sets the algorithm request bodies are compressed with, and that servers are asked to
+    /// compress responses with. Defaults to gzip.
+    pub fn set_http_compression_algorithm(mut self, algorithm: HttpCompressionAlgorithm) -> Self {
+        self.http_compression_algorithm = algorithm;
+        self
+    }
+
+    /// Sets the separator between the collection and the number of generated ids, such as the
+    /// `/` of `orders/1-A`.
+    pub fn set_identity_parts_separator(mut self, separator: char) -> Self {
+        self.identity_parts_separator = separator;
+        self
+    }
+
+    /// Sets how requests are distributed across the nodes of the topology.
+    pub fn set_load_balance_behavior(mut self, behavior: LoadBalanceBehavior) -> Self {
+        self.load_balance_behavior = behavior;
+        self
+    }
+
+    /// Sets the seed mixed into the hash of a session's load balancer context. Changing it
+    /// reshuffles which node each context is pinned to.
+    pub fn set_load_balancer_context_seed(mut self, seed: i32) -> Self {
+        self.load_balancer_context_seed = seed;
+        self
+    }
+
+    /// Sets how many bytes of response bodies each `RequestExecutor` keeps in its HTTP cache.
+    /// Defaults to 128 MB. Setting it to 0 disables the cache.
+    pub fn set_max_http_cache_size(mut self, bytes: usize) -> Self {
+        self.max_http_cache_size = bytes;
+        self
+    }
+
+    /// Sets how many requests a session may send before it fails, to catch sessions that are
+    /// kept open for too long or load documents one by one in a loop.
+    pub fn set_max_number_of_requests_per_session(mut self, max: usize) -> Self {
+        self.max_number_of_requests_per_session = max;
+        self
+    }
+
+    /// Sets how read-only requests are distributed across the nodes of the topology. Writes
+    /// always go to the preferred node.
+    pub fn set_read_balance_behavior(mut self, behavior: ReadBalanceBehavior) -> Self {
+        self.read_balance_behavior = behavior;
+        self
+    }
+
+    /// Sets how long each attempt to send a command may take before it is aborted and the next
+    /// node is tried. Commands can override it. There is no timeout unless one is set.
+    pub fn set_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether the executors identify themselves to the server, which then lists them
+    /// among the database's clients.
+    pub fn set_send_application_identified(mut self, send: bool) -> Self {
+        self.send_application_identified = send;
+        self
+    }
+
+    /// Sets the directory database and cluster topologies are cached in. The cached topologies
+    /// are used when none of the store's urls respond on startup. Caching is off unless a
+    /// directory is set.
+    pub fn set_topology_cache_location<P: Into<PathBuf>>(mut self, location: P) -> Self {
+        self.topology_cache_location = Some(location.into());
+        self
+    }
+
+    /// Sets whether request bodies and responses are compressed, with the algorithm set by
+    /// [`set_http_compression_algorithm`](Self::set_http_compression_algorithm). Defaults to true.
+    pub fn set_use_compression(mut self, use_compression: bool) -> Self {
+        self.use_compression = use_compression;
+        self
+    }
+
+    /// Sets how long saving changes may wait for the indexes to catch up.
+    ///
+    /// Reserved for when saving changes can wait for indexes. Nothing uses it yet.
+    pub fn set_wait_for_indexes_after_save_changes_timeout(mut self, timeout: Duration) -> Self {
+        self.wait_for_indexes_after_save_changes_timeout = timeout;
+        self
+    }
+
+    /// Returns a copy of these conventions with the values set in the server's client
+    /// configuration applied on top. A disabled configuration leaves the conventions unchanged.
+    pub(crate) fn with_client_configuration(
+        &self,
+        configuration: Option<&ClientConfiguration>,
+    ) -> Self {
+        let mut conventions = self.clone();
+        let Some(configuration) = configuration.filter(|c| !c.disabled) else {
+            return conventions;
+        };
+
+        if let Some(behavior) = configuration.load_balance_behavior {
+            conventions.load_balance_behavior = behavior;
+        }
+        if let Some(seed) = configuration.load_balancer_context_seed {
+            conventions.load_balancer_context_seed = seed;
+        }
+        if let Some(behavior) = configuration.read_balance_behavior {
+            conventions.read_balance_behavior = behavior;
+        }
+        if let Some(separator) = configuration.identity_parts_separator {
+            conventions.identity_parts_separator = separator;
+        }
+        // The server sends a signed number, of which only positive values make sense
+        if let Some(max) = configuration
+            .max_number_of_requests_per_session
+            .and_then(|max| usize::try_from(max).ok())
+        {
+            conventions.max_number_of_requests_per_session = max;
+        }
+        conventions
+    }
 }
 
 // Getters
 impl DocumentConventions {
+    pub fn cluster_topology_refresh_interval(&self) -> Duration {
+        self.cluster_topology_refresh_interval
+    }
+
+    pub fn database_topology_refresh_interval(&self) -> Duration {
+        self.database_topology_refresh_interval
+    }
+
+    pub fn topology_refresh_jitter(&self) -> Duration {
+        self.topology_refresh_jitter
+    }
+
     pub fn topology_updates_disabled(&self) -> bool {
         self.disable_topology_updates
     }
+
+    /// Returns the name of the collection entities of the type named `type_name` belong to.
+    pub fn find_collection_name(&self, type_name: &str) -> String {
+        (self.find_collection_name.0)(type_name)
+    }
+
+    pub fn first_broadcast_attempt_timeout(&self) -> Duration {
+        self.first_broadcast_attempt_timeout
+    }
+
+    pub fn http_compression_algorithm(&self) -> HttpCompressionAlgorithm {
+        self.http_compression_algorithm
+    }
+
+    pub fn identity_parts_separator(&self) -> char {
+        self.identity_parts_separator
+    }
+
+    pub fn load_balance_behavior(&self) -> LoadBalanceBehavior {
+        self.load_balance_behavior
+    }
+
+    pub fn load_balancer_context_seed(&self) -> i32 {
+        self.load_balancer_context_seed
+    }
+
+    pub fn max_http_cache_size(&self) -> usize {
+        self.max_http_cache_size
+    }
+
+    pub fn max_number_of_requests_per_session(&self) -> usize {
+        self.max_number_of_requests_per_session
+    }
+
+    pub fn read_balance_behavior(&self) -> ReadBalanceBehavior {
+        self.read_balance_behavior
+    }
+
+    pub fn request_timeout(&self) -> Option<Duration> {
+        self.request_timeout
+    }
+
+    pub fn send_application_identified(&self) -> bool {
+        self.send_application_identified
+    }
+
+    pub fn topology_cache_location(&self) -> Option<&Path> {
+        self.topology_cache_location.as_deref()
+    }
+
+    pub fn use_compression(&self) -> bool {
+        self.use_compression
+    }
+
+    pub fn wait_for_indexes_after_save_changes_timeout(&self) -> Duration {
+        self.wait_for_indexes_after_save_changes_timeout
+    }
+}
+
+#[derive(Clone)]
+struct CollectionNameFinder(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl Default for CollectionNameFinder {
+    fn default() -> Self {
+        Self(Arc::new(pluralize))
+    }
+}
+
+impl fmt::Debug for CollectionNameFinder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CollectionNameFinder(..)")
+    }
+}
+
+/// Pluralizes an English type name the way the official clients name collections, such as
+/// `Order` to `Orders` and `Company` to `Companies`.
+pub(crate) fn pluralize(name: &str) -> String {
+    let lowercase = name.to_ascii_lowercase();
+    let ends_with_consonant_y = lowercase.ends_with('y')
+        && !lowercase
+            .chars()
+            .rev()
+            .nth(1)
+            .map(|c| "aeiou".contains(c))
+            .unwrap_or(false);
+
+    if ends_with_consonant_y {
+        format!("{}ies", &name[..name.len() - 1])
+    } else if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| lowercase.ends_with(suffix))
+    {
+        format!("{}es", name)
+    } else {
+        format!("{}s", name)
+    }
+}
+
+/// Reads a duration written as a number of seconds, such as `2.5`.
+fn deserialize_secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    Duration::try_from_secs_f64(f64::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+fn deserialize_optional_secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Option::<f64>::deserialize(deserializer)?
+        .map(|secs| Duration::try_from_secs_f64(secs).map_err(D::Error::custom))
+        .transpose()
+}
+
+/// The compression used for request bodies and asked of the server for responses.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum HttpCompressionAlgorithm {
+    #[default]
+    Gzip,
+    Brotli,
+    /// Needs RavenDB 7.0 or later.
+    Zstd,
+}
+
+impl HttpCompressionAlgorithm {
+    /// Returns the name of the algorithm in `Content-Encoding` and `Accept-Encoding` headers.
+    pub(crate) fn content_encoding(self) -> &'static str {
+        match self {
+            HttpCompressionAlgorithm::Gzip => "gzip",
+            HttpCompressionAlgorithm::Brotli => "br",
+            HttpCompressionAlgorithm::Zstd => "zstd",
+        }
+    }
+}
+
+/// Determines how the `RequestExecutor` spreads requests, both reads and writes, across the nodes
+/// of a database's topology.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum LoadBalanceBehavior {
+    /// Requests are not load balanced and are routed by the other conventions.
+    #[default]
+    None,
+    /// Sessions that set a load balancer context, such as a tenant id, are pinned to the node
+    /// that context hashes to. All sessions of this library sharing a context use the same node,
+    /// so they can read their own writes. Affinity doesn't extend to the official .NET and Java
+    /// clients, which hash contexts differently.
+    UseSessionContext,
+}
+
+/// Determines which node the `RequestExecutor` sends read-only requests to. Writes always go to
+/// the preferred node regardless of this setting.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum ReadBalanceBehavior {
+    /// All reads go to the preferred node.
+    #[default]
+    None,
+    /// Reads are spread across the nodes. Requests from the same session stay on one node.
+    RoundRobin,
+    /// Reads go to the node that has been responding the fastest.
+    FastestNode,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client_configuration::ClientConfiguration;
+
+    use super::{pluralize, DocumentConventions, ReadBalanceBehavior};
+
+    #[test]
+    fn pluralize_follows_english_rules() {
+        assert_eq!(pluralize("Order"), "Orders");
+        assert_eq!(pluralize("Company"), "Companies");
+        assert_eq!(pluralize("Day"), "Days");
+        assert_eq!(pluralize("Address"), "Addresses");
+        assert_eq!(pluralize("Box"), "Boxes");
+    }
+
+    #[test]
+    fn find_collection_name_uses_the_configured_function() {
+        let conventions = DocumentConventions::default();
+        let custom = conventions
+            .clone()
+            .set_find_collection_name(|type_name| type_name.to_lowercase());
+
+        assert_eq!(conventions.find_collection_name("Company"), "Companies");
+        assert_eq!(custom.find_collection_name("Company"), "company");
+    }
+
+    #[test]
+    fn with_client_configuration_overrides_read_balance_behavior() {
+        // Arrange
+        let conventions = DocumentConventions::default();
+        let configuration = ClientConfiguration {
+            read_balance_behavior: Some(ReadBalanceBehavior::FastestNode),
+            ..Default::default()
+        };
+
+        // Act
+        let result = conventions.with_client_configuration(Some(&configuration));
+
+        // Assert
+        assert_eq!(
+            result.read_balance_behavior(),
+            ReadBalanceBehavior::FastestNode
+        );
+    }
+
+    #[test]
+    fn with_client_configuration_overrides_session_conventions() {
+        // Arrange
+        let conventions = DocumentConventions::default();
+        let configuration = ClientConfiguration {
+            identity_parts_separator: Some('|'),
+            max_number_of_requests_per_session: Some(100),
+            ..Default::default()
+        };
+
+        // Act
+        let result = conventions.with_client_configuration(Some(&configuration));
+
+        // Assert
+        assert_eq!(result.identity_parts_separator(), '|');
+        assert_eq!(result.max_number_of_requests_per_session(), 100);
+    }
+
+    #[test]
+    fn with_client_configuration_ignores_disabled_configuration() {
+        // Arrange
+        let conventions = DocumentConventions::default()
+            .set_read_balance_behavior(ReadBalanceBehavior::RoundRobin);
+        let configuration = ClientConfiguration {
+            disabled: true,
+            read_balance_behavior: Some(ReadBalanceBehavior::FastestNode),
+            ..Default::default()
+        };
+
+        // Act
+        let result = conventions.with_client_configuration(Some(&configuration));
+
+        // Assert
+        assert_eq!(
+            result.read_balance_behavior(),
+            ReadBalanceBehavior::RoundRobin
+        );
+    }
 }
@@ -1,39 +1,141 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::Context;
+use serde_json::{json, Value};
 use tracing::instrument;
 
 use crate::{
     cluster_topology::ClusterTopologyInfo,
-    raven_command::{RavenCommand, RavenCommandVariant},
-    DocumentStore,
+    raven_command::{BatchCommand, GetAllDocumentsCommand, GetClusterTopologyCommand},
+    DocumentStore, RavenEntity, RequestExecutor,
 };
 
 /// Implements Unit of Work for accessing the RavenDB server.
 #[derive(Debug)]
 pub struct DocumentSession {
     document_store: DocumentStore,
+    /// Requests sent so far, capped by the store's
+    /// [`max_number_of_requests_per_session`](crate::DocumentConventions::max_number_of_requests_per_session).
+    number_of_requests: AtomicUsize,
+    /// Documents stored since the last [`save_changes`](DocumentSession::save_changes), by id.
+    pending_documents: Vec<(String, Value)>,
+    session_info: SessionInfo,
 }
 
 impl DocumentSession {
     #[allow(clippy::new_without_default)]
     pub fn new(document_store: DocumentStore) -> Self {
-        Self { document_store }
+        Self {
+            document_store,
+            number_of_requests: AtomicUsize::new(0),
+            pending_documents: Vec::new(),
+            session_info: SessionInfo::new(),
+        }
     }
 
-    #[instrument(level = "info", name = "Get Cluster Topology", skip(self))]
-    pub async fn get_cluster_topology(&self) -> anyhow::Result<ClusterTopologyInfo> {
-        let raven_command = RavenCommand {
-            base_server_url: self.document_store.get_server_address().await?,
-            command: RavenCommandVariant::GetClusterTopology,
+    pub fn number_of_requests(&self) -> usize {
+        self.number_of_requests.load(Ordering::Relaxed)
+    }
+
+    /// Counts a request about to be sent through `executor`, failing once the session sent as
+    /// many as the conventions allow. The server's client configuration may override the limit.
+    async fn increment_requests_count(&self, executor: &RequestExecutor) -> anyhow::Result<()> {
+        let max = executor
+            .conventions()
+            .await?
+            .max_number_of_requests_per_session();
+        let count = self.number_of_requests.fetch_add(1, Ordering::Relaxed) + 1;
+        if count > max {
+            anyhow::bail!(
+                "The maximum number of requests ({}) allowed for this session has been reached. \
+                Sessions are meant to be short lived units of work; consider opening a new one \
+                or batching the work, or raise `max_number_of_requests_per_session`.",
+                max
+            );
+        }
+        Ok(())
+    }
+
+    /// Pins this session to a node chosen from the given context, such as a tenant id, when the
+    /// store uses [`LoadBalanceBehavior::UseSessionContext`](crate::LoadBalanceBehavior). Sessions
+    /// opened with the same context are routed to the same node.
+    pub fn set_load_balancer_context(&mut self, context: &str) {
+        self.session_info.set_load_balancer_context(context);
+    }
+
+    pub fn session_info(&self) -> &SessionInfo {
+        &self.session_info
+    }
+
+    /// Stores the entity in its collection on the next [`save_changes`](DocumentSession::save_changes).
+    ///
+    /// Entities without an id get one generated on the client, such as `orders/1-A`, which is
+    /// written back through [`RavenEntity::set_id`]. Storing an entity again replaces the pending
+    /// document with the same id.
+    #[instrument(level = "debug", name = "Store Entity", skip_all)]
+    pub async fn store<T: RavenEntity>(&mut self, entity: &mut T) -> anyhow::Result<()> {
+        let collection_name = T::collection_name(self.document_store.conventions());
+        let id = match entity.id() {
+            Some(id) => id.to_string(),
+            None => {
+                let id = self
+                    .document_store
+                    .generate_document_id(&collection_name)
+                    .await?;
+                entity.set_id(id.clone());
+                id
+            }
+        };
+
+        let mut document = serde_json::to_value(&*entity)
+            .with_context(|| format!("Could not serialize document `{}`", id))?;
+        let Value::Object(fields) = &mut document else {
+            anyhow::bail!("Document `{}` must serialize to a JSON object", id);
         };
+        fields.insert(
+            "@metadata".to_string(),
+            json!({ "@collection": collection_name }),
+        );
+
+        self.pending_documents
+            .retain(|(pending_id, _)| !pending_id.eq_ignore_ascii_case(&id));
+        self.pending_documents.push((id, document));
+        Ok(())
+    }
+
+    /// Sends every document stored since the last call to the server in a single batch.
+    #[instrument(level = "info", name = "Save Changes", skip(self))]
+    pub async fn save_changes(&mut self) -> anyhow::Result<()> {
+        if self.pending_documents.is_empty() {
+            return Ok(());
+        }
+
+        let executor = self.document_store.get_request_executor(None).await?;
+        self.increment_requests_count(&executor).await?;
+        let commands = self
+            .pending_documents
+            .iter()
+            .map(|(id, document)| json!({ "Id": id, "Type": "PUT", "Document": document }))
+            .collect();
+        executor
+            .execute(BatchCommand { commands }, Some(self.session_info.clone()))
+            .await?;
 
-        // let response = self
-        //     .document_store
-        //     .execute_raven_command(raven_command)
-        //     .await?;
+        tracing::info!("Saved {} documents", self.pending_documents.len());
+        self.pending_documents.clear();
+        Ok(())
+    }
 
-        // let topology = response.json::<ClusterTopologyInfo>().await?;
-        // tracing::info!("Cluster topology downloaded");
-        // Ok(topology)
-        todo!()
+    #[instrument(level = "info", name = "Get Cluster Topology", skip(self))]
+    pub async fn get_cluster_topology(&self) -> anyhow::Result<ClusterTopologyInfo> {
+        let executor = self.document_store.get_request_executor(None).await?;
+        self.increment_requests_count(&executor).await?;
+        let topology = executor
+            .execute(GetClusterTopologyCommand, Some(self.session_info.clone()))
+            .await?;
+
+        tracing::info!("Cluster topology downloaded");
+        Ok(topology)
     }
 
     #[instrument(level = "info", name = "Get All Documents for Database", skip(self))]
@@ -43,26 +145,225 @@ impl DocumentSession {
         page_size: Option<i64>,
         start: Option<i64>,
     ) -> anyhow::Result<String> {
-        let raven_command = RavenCommand {
-            base_server_url: self.document_store.get_server_address().await?,
-            command: RavenCommandVariant::GetAllDocumentsFromDatabase {
-                database: database.to_string(),
-                page_size,
-                start,
-            },
-        };
+        let executor = self
+            .document_store
+            .get_request_executor(Some(database.to_string()))
+            .await?;
+        self.increment_requests_count(&executor).await?;
+        let documents = executor
+            .execute(
+                GetAllDocumentsCommand { page_size, start },
+                Some(self.session_info.clone()),
+            )
+            .await?;
 
-        // let response = self
-        //     .document_store
-        //     .execute_raven_command(raven_command)
-        //     .await?;
-
-        // let topology = response.text().await?;
-        // tracing::info!("Got documents from database `{}`", database);
-        // Ok(topology)
-        todo!()
+        tracing::info!("Got documents from database `{}`", database);
+        Ok(documents.to_string())
     }
 }
 
 #[derive(Debug)]
 pub struct RavenDbVersion(String);
+
+/// Identifies a [`DocumentSession`] to the request executor so requests can be routed to a node
+/// based on the session rather than on each individual request.
+#[derive(Clone, Debug)]
+pub struct SessionInfo {
+    session_id: i32,
+    load_balancer_context: Option<String>,
+}
+
+impl SessionInfo {
+    pub fn new() -> Self {
+        Self {
+            session_id: rand::random(),
+            load_balancer_context: None,
+        }
+    }
+
+    /// Returns the randomly assigned id of this session.
+    pub fn session_id(&self) -> i32 {
+        self.session_id
+    }
+
+    pub fn set_load_balancer_context(&mut self, context: &str) {
+        self.load_balancer_context = Some(context.to_string());
+    }
+
+    /// Returns the session id derived from the load balancer context, if one was set.
+    ///
+    /// Unlike [`SessionInfo::session_id`] this is stable across sessions and processes, so every
+    /// session of this library sharing a context maps to the same node. Sessions of the official
+    /// clients hash contexts differently and may be pinned elsewhere.
+    pub fn context_session_id(&self, seed: i32) -> Option<i32> {
+        self.load_balancer_context
+            .as_deref()
+            .map(|context| hash_load_balancer_context(context, seed))
+    }
+}
+
+impl Default for SessionInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hashes the context with 32 bit FNV-1a, starting from an offset basis mixed with the seed.
+///
+/// [`std::collections::hash_map::DefaultHasher`] is not used because its output is not guaranteed
+/// to be the same between Rust releases, and every process using this library has to agree.
+/// This is not the hash the official .NET and Java clients use, so they may pin the same context
+/// to a different node.
+fn hash_load_balancer_context(context: &str, seed: i32) -> i32 {
+    const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+    const FNV_PRIME: u32 = 0x0100_0193;
+
+    let hash = context
+        .as_bytes()
+        .iter()
+        .fold(FNV_OFFSET_BASIS ^ seed as u32, |hash, byte| {
+            (hash ^ u32::from(*byte)).wrapping_mul(FNV_PRIME)
+        });
+    hash as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+    use wiremock::{
+        matchers::{body_string_contains, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{DocumentConventions, DocumentStoreBuilder, RavenEntity};
+
+    use super::SessionInfo;
+
+    #[derive(Serialize)]
+    struct Order {
+        id: Option<String>,
+        total: f64,
+    }
+
+    impl RavenEntity for Order {
+        fn id(&self) -> Option<&str> {
+            self.id.as_deref()
+        }
+
+        fn set_id(&mut self, id: String) {
+            self.id = Some(id);
+        }
+    }
+
+    /// Mounts a topology on `server` made of itself, tagged A, for the database `db`.
+    async fn mount_topology(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Nodes": [
+                    { "Url": server.uri(), "ClusterTag": "A", "Database": "db", "ServerRole": "Member" }
+                ],
+                "Etag": 1
+            })))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn store_generates_an_id_and_save_changes_sends_the_collection() {
+        // Arrange
+        let server = MockServer::start().await;
+        mount_topology(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/databases/db/hilo/next"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Prefix": "orders/", "Low": 1, "High": 32, "LastSize": 32,
+                "ServerTag": "A", "LastRangeAt": null
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/databases/db/bulk_docs"))
+            .and(body_string_contains(r#""@collection":"Orders""#))
+            .and(body_string_contains(r#""Id":"orders/1-A""#))
+            .respond_with(
+                ResponseTemplate::new(201).set_body_json(serde_json::json!({ "Results": [] })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let document_store = DocumentStoreBuilder::new()
+            .set_urls(&[server.uri()])
+            .set_database_name("db")
+            .build()
+            .unwrap();
+        let mut session = document_store.open_session().unwrap();
+        let mut order = Order {
+            id: None,
+            total: 12.5,
+        };
+
+        // Act
+        session.store(&mut order).await.unwrap();
+        session.save_changes().await.unwrap();
+
+        // Assert
+        assert_eq!(order.id.as_deref(), Some("orders/1-A"));
+    }
+
+    #[tokio::test]
+    async fn session_fails_once_max_number_of_requests_is_reached() {
+        // Arrange
+        let server = MockServer::start().await;
+        mount_topology(&server).await;
+        let document_store = DocumentStoreBuilder::new()
+            .set_urls(&[server.uri()])
+            .set_database_name("db")
+            .set_conventions(
+                DocumentConventions::default().set_max_number_of_requests_per_session(0),
+            )
+            .build()
+            .unwrap();
+        let session = document_store.open_session().unwrap();
+
+        // Act
+        let result = session.get_cluster_topology().await;
+
+        // Assert
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("maximum number of requests (0)"));
+        assert_eq!(session.number_of_requests(), 1);
+    }
+
+    #[test]
+    fn context_session_id_is_the_same_for_the_same_context() {
+        // Arrange
+        let mut first = SessionInfo::new();
+        let mut second = SessionInfo::new();
+        first.set_load_balancer_context("tenants/1");
+        second.set_load_balancer_context("tenants/1");
+
+        // Assert
+        assert_eq!(first.context_session_id(0), second.context_session_id(0));
+    }
+
+    #[test]
+    fn context_session_id_depends_on_the_seed() {
+        // Arrange
+        let mut session_info = SessionInfo::new();
+        session_info.set_load_balancer_context("tenants/1");
+
+        // Assert
+        assert_ne!(
+            session_info.context_session_id(0),
+            session_info.context_session_id(1)
+        );
+    }
+
+    #[test]
+    fn context_session_id_is_none_without_context() {
+        assert!(SessionInfo::new().context_session_id(0).is_none());
+    }
+}
@@ -1,10 +1,16 @@
+mod client_certificate;
+mod connection_string;
 mod document_store_actor;
 mod document_store_builder;
+mod document_store_config;
 mod document_store_error;
 mod document_store_handle;
+mod hilo_id_generator;
 
+pub use client_certificate::*;
 pub use document_store_actor::*;
 pub use document_store_builder::*;
+pub use document_store_config::*;
 pub use document_store_error::*;
 pub use document_store_handle::*;
 
@@ -12,7 +18,10 @@ use reqwest::Url;
 
 use tokio::sync::oneshot;
 
-use crate::{request_executor::RequestExecutor, DnsOverrides};
+use crate::{
+    request_executor::{HttpClientSettings, RequestExecutor},
+    DocumentConventions,
+};
 
 #[derive(Debug)]
 pub enum DocumentStoreMessage {
@@ -32,6 +41,11 @@ pub enum DocumentStoreMessage {
     GetServerAddress {
         respond_to: oneshot::Sender<Result<Url, anyhow::Error>>,
     },
+    /// Makes every request executor present a new client certificate.
+    ReplaceClientCertificate {
+        certificate: ClientCertificate,
+        respond_to: oneshot::Sender<Result<(), DocumentStoreError>>,
+    },
     // UpdateTopology,
 }
 
@@ -39,18 +53,17 @@ pub enum DocumentStoreMessage {
 #[derive(Debug)]
 pub struct DocumentStoreInitialConfiguration {
     //async_document_id_generator: Box<dyn AsyncDocumentIdGenerator>,
-    pub(crate) client_identity: Option<reqwest::Identity>,
+    pub(crate) conventions: DocumentConventions,
     // pub(crate) cluster_topology: ClusterTopologyInfo,
     pub(crate) initial_urls: Vec<Url>,
     pub(crate) database_name: Option<String>,
-    pub(crate) dns_overrides: DnsOverrides,
-    pub(crate) proxy_address: Option<String>,
+    /// Certificates, dns overrides and proxy of every executor's HTTP client.
+    pub(crate) http_client_settings: HttpClientSettings,
 }
 
 // Placeholders below
 #[derive(Debug)]
 pub struct Conventions;
-pub struct CertificatePlaceholder;
 
 pub struct DatabaseChanges;
 pub struct DatabaseChangesBuilder;
@@ -0,0 +1,165 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::DocumentStoreError;
+
+/// The certificate a [`DocumentStore`](crate::DocumentStore) authenticates to a secured server
+/// with.
+///
+/// PEM certificates hold the certificate and its private key. PFX (PKCS#12) certificates are the
+/// ones RavenDB Cloud and the setup wizard hand out, protected by a password.
+#[derive(Clone)]
+pub enum ClientCertificate {
+    PemFile(PathBuf),
+    Pem(Vec<u8>),
+    PfxFile { path: PathBuf, password: String },
+    Pfx { bytes: Vec<u8>, password: String },
+}
+
+impl ClientCertificate {
+    /// The certificate file at `path`. It is read as PFX if it has a `.pfx` or `.p12` extension
+    /// or a password, and as PEM otherwise.
+    pub(crate) fn from_path(path: PathBuf, password: Option<String>) -> Self {
+        let is_pfx = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| ["pfx", "p12"].contains(&extension.to_ascii_lowercase().as_str()))
+            .unwrap_or(false);
+        match password {
+            Some(password) => ClientCertificate::PfxFile { path, password },
+            None if is_pfx => ClientCertificate::PfxFile {
+                path,
+                password: String::new(),
+            },
+            None => ClientCertificate::PemFile(path),
+        }
+    }
+
+    /// Reads and decrypts the certificate, and returns its chain and private key as PEM once it
+    /// proved to be an identity the HTTP client can present.
+    pub(crate) fn to_pem(&self) -> Result<Vec<u8>, DocumentStoreError> {
+        let result = match self {
+            ClientCertificate::PemFile(path) => read_file(path),
+            ClientCertificate::Pem(pem) => Ok(pem.clone()),
+            ClientCertificate::PfxFile { path, password } => {
+                read_file(path).and_then(|pfx| pfx_to_pem(&pfx, password))
+            }
+            ClientCertificate::Pfx { bytes, password } => pfx_to_pem(bytes, password),
+        }
+        .and_then(|pem| {
+            reqwest::Identity::from_pem(&pem).context("Invalid PEM certificate")?;
+            Ok(pem)
+        });
+
+        result.map_err(|e| {
+            tracing::error!("Invalid client certificate. Caused by: {:?}", e);
+            DocumentStoreError::InvalidClientCertificate(e)
+        })
+    }
+}
+
+// Keeps the key material and passwords out of logs
+impl fmt::Debug for ClientCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientCertificate::PemFile(path) => f.debug_tuple("PemFile").field(path).finish(),
+            ClientCertificate::Pem(_) => f.write_str("Pem(..)"),
+            ClientCertificate::PfxFile { path, .. } => f
+                .debug_struct("PfxFile")
+                .field("path", path)
+                .finish_non_exhaustive(),
+            ClientCertificate::Pfx { .. } => f.write_str("Pfx { .. }"),
+        }
+    }
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    std::fs::read(path)
+        .with_context(|| format!("Failed to read certificate file `{}`", path.display()))
+}
+
+/// Converts the certificate chain and private key of a PFX file to PEM, as the TLS backend
+/// only reads identities in that format.
+fn pfx_to_pem(pfx: &[u8], password: &str) -> anyhow::Result<Vec<u8>> {
+    let key_store = p12_keystore::KeyStore::from_pkcs12(pfx, password)
+        .map_err(|e| anyhow::anyhow!("{}", e))
+        .context("Unable to read the PFX certificate. Is the password correct?")?;
+    let (_, key_chain) = key_store
+        .private_key_chain()
+        .context("The PFX certificate has no private key")?;
+
+    let mut pem = to_pem("PRIVATE KEY", key_chain.key());
+    for certificate in key_chain.chain() {
+        pem.push_str(&to_pem("CERTIFICATE", certificate.as_der()));
+    }
+    Ok(pem.into_bytes())
+}
+
+fn to_pem(label: &str, der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(&String::from_utf8_lossy(line));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DocumentStoreError;
+
+    use super::ClientCertificate;
+
+    #[test]
+    fn to_pem_reads_pfx_with_password() {
+        // Arrange
+        let certificate = ClientCertificate::PfxFile {
+            path: "../ravendb-client_dev_cert.pfx".into(),
+            password: "ravendb".to_string(),
+        };
+
+        // Act
+        let result = certificate.to_pem();
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn to_pem_fails_for_wrong_pfx_password() {
+        // Arrange
+        let bytes = std::fs::read("../ravendb-client_dev_cert.pfx").unwrap();
+        let certificate = ClientCertificate::Pfx {
+            bytes,
+            password: "wrong".to_string(),
+        };
+
+        // Act
+        let result = certificate.to_pem();
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(DocumentStoreError::InvalidClientCertificate(_))
+        ));
+    }
+
+    #[test]
+    fn to_pem_reads_pem_bytes() {
+        // Arrange
+        let pem = std::fs::read("../ravendb-client_dev_cert.pem").unwrap();
+
+        // Act
+        let result = ClientCertificate::Pem(pem).to_pem();
+
+        // Assert
+        assert!(result.is_ok());
+    }
+}
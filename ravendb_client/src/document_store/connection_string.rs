@@ -0,0 +1,113 @@
+//! Parses RavenDB connection strings, such as
+//! `Url=https://a.example.com,https://b.example.com;Database=Orders;Certificate=/path.pfx;Password=secret`.
+
+use std::{fmt, path::PathBuf};
+
+use crate::DocumentStoreError;
+
+/// The settings of a connection string. Keys are case insensitive, and `Urls` is accepted for
+/// `Url`.
+#[derive(Default, PartialEq)]
+pub(crate) struct ConnectionString {
+    pub(crate) urls: Vec<String>,
+    pub(crate) database: Option<String>,
+    pub(crate) certificate: Option<PathBuf>,
+    pub(crate) password: Option<String>,
+}
+
+impl ConnectionString {
+    pub(crate) fn parse(connection_string: &str) -> Result<Self, DocumentStoreError> {
+        let mut parsed = Self::default();
+
+        for setting in connection_string.split(';').map(str::trim) {
+            if setting.is_empty() {
+                continue;
+            }
+            let (key, value) = setting.split_once('=').ok_or_else(|| {
+                invalid(format!("`{}` is not a `Key=Value` pair", redact(setting)))
+            })?;
+            let value = value.trim();
+
+            match key.trim().to_ascii_lowercase().as_str() {
+                "url" | "urls" => parsed.urls.extend(
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|url| !url.is_empty())
+                        .map(str::to_string),
+                ),
+                "database" => parsed.database = Some(value.to_string()),
+                "certificate" => parsed.certificate = Some(value.into()),
+                "password" => parsed.password = Some(value.to_string()),
+                _ => return Err(invalid(format!("Unknown key `{}`", key.trim()))),
+            }
+        }
+
+        if parsed.password.is_some() && parsed.certificate.is_none() {
+            return Err(invalid(
+                "`Password` is set without a `Certificate`".to_string(),
+            ));
+        }
+        Ok(parsed)
+    }
+}
+
+// Keeps the certificate password out of logs
+impl fmt::Debug for ConnectionString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionString")
+            .field("urls", &self.urls)
+            .field("database", &self.database)
+            .field("certificate", &self.certificate)
+            .field("password", &self.password.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+fn invalid(reason: String) -> DocumentStoreError {
+    tracing::error!("Invalid connection string. {}", reason);
+    DocumentStoreError::InvalidConnectionString(reason)
+}
+
+/// Hides the value of a malformed setting, in case it is a password.
+fn redact(setting: &str) -> String {
+    setting.chars().take(3).chain("..".chars()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DocumentStoreError;
+
+    use super::ConnectionString;
+
+    #[test]
+    fn parse_reads_every_key() {
+        // Act
+        let result = ConnectionString::parse(
+            "Url=https://a, https://b ;database=Orders;Certificate=/certs/app.pfx;Password=p=w;",
+        )
+        .unwrap();
+
+        // Assert
+        assert_eq!(result.urls, vec!["https://a", "https://b"]);
+        assert_eq!(result.database.as_deref(), Some("Orders"));
+        assert_eq!(result.certificate, Some("/certs/app.pfx".into()));
+        assert_eq!(result.password.as_deref(), Some("p=w"));
+    }
+
+    #[test]
+    fn parse_fails_for_unknown_key() {
+        assert!(matches!(
+            ConnectionString::parse("Url=http://a;Server=b"),
+            Err(DocumentStoreError::InvalidConnectionString(_))
+        ));
+    }
+
+    #[test]
+    fn parse_fails_for_password_without_certificate() {
+        assert!(matches!(
+            ConnectionString::parse("Url=https://a;Password=secret"),
+            Err(DocumentStoreError::InvalidConnectionString(_))
+        ));
+    }
+}
@@ -6,25 +6,23 @@ use tracing::{instrument, Span};
 use uuid::Uuid;
 
 use crate::{
-    document_conventions::DocumentConventions, request_executor::RequestExecutor,
-    CertificatePlaceholder, DnsOverrides, DocumentStoreError, DocumentStoreInitialConfiguration,
-    DocumentStoreMessage,
+    document_conventions::DocumentConventions,
+    request_executor::{HttpClientSettings, RequestExecutor, RequestExecutorError},
+    ClientCertificate, DocumentStoreError, DocumentStoreInitialConfiguration, DocumentStoreMessage,
 };
 
 pub struct DocumentStoreActor {
-    client_identity: Option<reqwest::Identity>,
-    dns_overrides: DnsOverrides,
     conventions: DocumentConventions,
     database_name: Option<String>,
+    /// Certificates, dns overrides and proxy every executor's HTTP client is built with.
+    http_client_settings: HttpClientSettings,
     initial_urls: Vec<Url>,
-    proxy_address: Option<String>,
     receiver: mpsc::Receiver<DocumentStoreMessage>,
     /// Allows the actor to receive messages from itself.
     receiver_internal: mpsc::Receiver<DocumentStoreMessage>,
     request_executors: HashMap<String, RequestExecutor>,
     /// Allows the actor to send messages to itself.
     sender_internal: mpsc::Sender<DocumentStoreMessage>,
-    _trust_store: Option<CertificatePlaceholder>,
     // topology_info: ClusterTopologyInfo,
     // topology_updater: Option<JoinHandle<Result<ClusterTopologyInfo, DocumentStoreError>>>,
 }
@@ -35,17 +33,14 @@ impl DocumentStoreActor {
     ) -> Self {
         let (tx, rx) = mpsc::channel(10);
         Self {
-            conventions: DocumentConventions::default(),
-            client_identity: initial_config.client_identity,
+            conventions: initial_config.conventions,
             database_name: initial_config.database_name,
-            dns_overrides: initial_config.dns_overrides,
+            http_client_settings: initial_config.http_client_settings,
             initial_urls: initial_config.initial_urls,
-            proxy_address: initial_config.proxy_address,
             receiver,
             receiver_internal: rx,
             request_executors: HashMap::default(),
             sender_internal: tx,
-            _trust_store: Some(CertificatePlaceholder),
             // topology_info: initial_config.cluster_topology,
             // topology_updater: None,
         }
@@ -118,6 +113,13 @@ impl DocumentStoreActor {
             DocumentStoreMessage::GetServerAddress { respond_to } => {
                 let result = self.get_server_address().await;
                 let _ = respond_to.send(result);
+            }
+            DocumentStoreMessage::ReplaceClientCertificate {
+                certificate,
+                respond_to,
+            } => {
+                let result = self.replace_client_certificate(certificate).await;
+                let _ = respond_to.send(result);
             } // DocumentStoreMessage::UpdateTopology => {
               //     tracing::debug!("Updating topology.");
               //     match self.refresh_topology().await {
@@ -249,6 +251,7 @@ impl DocumentStoreActor {
     // }
 
     /// See doc comments for [`DocumentStore::get_request_executor`](crate::DocumentStore::get_request_executor)
+    #[allow(clippy::empty_line_after_outer_attr)]
     #[instrument(level = "debug", skip(self))]
     async fn get_request_executor(
         &mut self,
@@ -281,31 +284,35 @@ impl DocumentStoreActor {
 
         // };
 
-        // Creates a RequestExecutor for a normal cluster
-        let create_request_executor = || -> RequestExecutor {
+        // Creates a request executor for a single, specific server, ignoring topology
+        let create_request_executor_for_single_node =
+            |url: Url| -> Result<RequestExecutor, RequestExecutorError> {
+                // TODO: Figure out how to allow the request executor to publish events
+                RequestExecutor::new_for_single_node_with_configuration_updates(
+                    url,
+                    database.clone(),
+                    self.http_client_settings.clone(),
+                    self.conventions.clone(),
+                )
+            };
+
+        let executor = if self.conventions.topology_updates_disabled() {
+            // Like the official clients, only the first url is used when topology updates are
+            // disabled. It is expected to point at a load balancer or a single server.
+            let url = self.initial_urls.first().cloned().ok_or_else(|| {
+                tracing::error!("No URLs available to create a single node request executor");
+                DocumentStoreError::MissingUrlsError
+            })?;
+            create_request_executor_for_single_node(url)?
+        } else {
             // TODO: Figure out how to allow the request executor to publish events
-            // todo!()
             RequestExecutor::new(
                 self.initial_urls.clone(),
                 database.clone(),
-                self.dns_overrides.clone(),
-                self.proxy_address.clone(),
-                self.client_identity.clone(),
+                self.http_client_settings.clone(),
                 self.conventions.clone(),
             )
-        };
-
-        // Creates a request executor for a single, specific server, ignoring topology
-        let create_request_executor_for_single_node = || -> RequestExecutor {
-            // TODO: Figure out how to allow the request executor to publish events
-            todo!()
-            //RequestExecutor::new_for_single_node_with_configuration_updates()
-        };
-
-        let executor = if self.conventions.topology_updates_disabled() {
-            create_request_executor_for_single_node()
-        } else {
-            create_request_executor()
+            .await?
         };
 
         // Clone the executor handle store it in the document store
@@ -315,6 +322,54 @@ impl DocumentStoreActor {
         Ok(executor)
     }
 
+    /// See doc comments for [`DocumentStore::replace_client_certificate`](crate::DocumentStore::replace_client_certificate)
+    #[instrument(level = "debug", skip(self))]
+    async fn replace_client_certificate(
+        &mut self,
+        certificate: ClientCertificate,
+    ) -> Result<(), DocumentStoreError> {
+        let client_certificate_pem = Some(certificate.to_pem()?);
+
+        // Switch every executor first, so a failure leaves the store on a single certificate
+        let mut replaced = Vec::new();
+        for executor in self.request_executors.values() {
+            if let Err(e) = executor
+                .replace_client_certificate(client_certificate_pem.clone())
+                .await
+            {
+                for executor in replaced {
+                    Self::restore_client_certificate(
+                        executor,
+                        &self.http_client_settings.client_certificate_pem,
+                    )
+                    .await;
+                }
+                return Err(e.into());
+            }
+            replaced.push(executor);
+        }
+
+        // Executors created from now on get the new certificate right away
+        self.http_client_settings.client_certificate_pem = client_certificate_pem;
+        Ok(())
+    }
+
+    /// Puts the previous certificate back on an executor after replacing it failed on another.
+    async fn restore_client_certificate(
+        executor: &RequestExecutor,
+        client_certificate_pem: &Option<Vec<u8>>,
+    ) {
+        if let Err(e) = executor
+            .replace_client_certificate(client_certificate_pem.clone())
+            .await
+        {
+            tracing::error!(
+                "Could not restore the previous client certificate of an executor. Caused by: {}",
+                e
+            );
+        }
+    }
+
     #[instrument(
         level = "debug",
         name = "DocumentStore Actor - Get Server Address",
@@ -1,17 +1,38 @@
-use std::{collections::HashMap, fs::File, io::Read, net::IpAddr};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+};
 
+use anyhow::Context;
 use reqwest::Url;
 use tracing::instrument;
 
-use crate::{DnsOverrides, DocumentStore, DocumentStoreError, DocumentStoreInitialConfiguration};
+use super::connection_string::ConnectionString;
+use crate::{
+    request_executor::{HttpClientSettings, TrustStore},
+    ClientCertificate, DnsOverrides, DocumentConventions, DocumentStore, DocumentStoreConfig,
+    DocumentStoreError, DocumentStoreInitialConfiguration, InvalidUrl, ProxySettings,
+};
 
 #[derive(Debug)]
 pub struct DocumentStoreBuilder {
-    client_certificate_path: Option<String>,
+    client_certificate: Option<ClientCertificate>,
+    conventions: DocumentConventions,
     database_name: Option<String>,
-    dns_overrides: HashMap<String, String>,
+    dns_overrides: HashMap<String, Vec<String>>,
     document_store_urls: Vec<String>,
-    proxy_address: Option<String>,
+    pinned_server_thumbprints: Vec<String>,
+    proxy: Option<ProxySettings>,
+    trusted_ca_certificates: Vec<TrustedCaCertificates>,
+    allow_http_with_certificate: bool,
+}
+
+/// Where a PEM bundle of CA certificates to trust is read from.
+#[derive(Debug)]
+enum TrustedCaCertificates {
+    PemFile(PathBuf),
+    Pem(Vec<u8>),
 }
 
 impl DocumentStoreBuilder {
@@ -19,24 +40,195 @@ impl DocumentStoreBuilder {
         Self::default()
     }
 
-    pub fn add_dns_override<S: Into<String>>(mut self, server: S, ip_address: S) -> Self {
-        self.dns_overrides.insert(server.into(), ip_address.into());
+    /// Creates a builder from a connection string such as
+    /// `Url=https://a.example.com,https://b.example.com;Database=Orders;Certificate=/path.pfx;Password=secret`.
+    ///
+    /// `Certificate` is read as PFX if it ends with `.pfx` or `.p12` or a `Password` is given,
+    /// and as PEM otherwise. The settings are validated by [`build`](Self::build), like ones
+    /// set by hand.
+    pub fn from_connection_string(connection_string: &str) -> Result<Self, DocumentStoreError> {
+        let connection_string = ConnectionString::parse(connection_string)?;
+        tracing::trace!("Parsed connection string: {:?}", &connection_string);
+
+        Ok(Self::new().set_connection_string(connection_string))
+    }
+
+    /// Creates a builder from the `RAVEN_URLS` (comma separated), `RAVEN_DATABASE`,
+    /// `RAVEN_CERT`, `RAVEN_CERT_PASSWORD` and `RAVEN_PROXY` environment variables. Unset ones
+    /// are left out.
+    ///
+    /// `RAVEN_CERT` is read like the `Certificate` of
+    /// [`from_connection_string`](Self::from_connection_string).
+    pub fn from_env() -> Result<Self, DocumentStoreError> {
+        Self::from_env_with(|name| std::env::var(name))
+    }
+
+    /// Like [`from_env`](Self::from_env), but reads the variables through `lookup`, such as
+    /// one backed by a `.env` file, instead of from the process environment.
+    pub fn from_env_with<F>(lookup: F) -> Result<Self, DocumentStoreError>
+    where
+        F: Fn(&str) -> Result<String, std::env::VarError>,
+    {
+        let env_var = |name| env_var(&lookup, name);
+        let urls = env_var("RAVEN_URLS")?
+            .map(|urls| {
+                urls.split(',')
+                    .map(str::trim)
+                    .filter(|url| !url.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let connection_string = ConnectionString {
+            urls,
+            database: env_var("RAVEN_DATABASE")?,
+            certificate: env_var("RAVEN_CERT")?.map(PathBuf::from),
+            password: env_var("RAVEN_CERT_PASSWORD")?,
+        };
+        tracing::trace!(
+            "Read connection settings from the environment: {:?}",
+            &connection_string
+        );
+
+        let mut builder = Self::new().set_connection_string(connection_string);
+        builder.proxy = env_var("RAVEN_PROXY")?.map(|address| ProxySettings::new(&address));
+        Ok(builder)
+    }
+
+    /// Creates a builder from a [`DocumentStoreConfig`], such as one read from a config file.
+    /// The settings are validated by [`build`](Self::build), like ones set by hand.
+    pub fn from_config(config: DocumentStoreConfig) -> Self {
+        Self {
+            client_certificate: config.certificate.map(ClientCertificate::from),
+            conventions: config.conventions,
+            database_name: config.database,
+            dns_overrides: config.dns_overrides,
+            document_store_urls: config.urls,
+            proxy: config.proxy,
+            allow_http_with_certificate: config.allow_http_with_certificate,
+            ..Self::default()
+        }
+    }
+
+    fn set_connection_string(mut self, connection_string: ConnectionString) -> Self {
+        self.document_store_urls = connection_string.urls;
+        self.database_name = connection_string.database;
+        self.client_certificate = connection_string
+            .certificate
+            .map(|path| ClientCertificate::from_path(path, connection_string.password));
         self
     }
 
+    /// Connects to `address` in place of the addresses DNS resolves `server` to.
+    ///
+    /// `address` is an IP address, optionally with a port such as `127.0.0.1:8081`. Requests to
+    /// `server` are then sent to that port rather than the url's. Call this more than once for a
+    /// server to give it several addresses, which are tried in order.
+    ///
+    /// All addresses of a server must use the same port, as the port is chosen per request rather
+    /// than per address. Building the store fails with
+    /// [`DocumentStoreError::InvalidDnsOverride`] otherwise.
+    pub fn add_dns_override<S: Into<String>>(mut self, server: S, address: S) -> Self {
+        self.dns_overrides
+            .entry(server.into())
+            .or_default()
+            .push(address.into());
+        self
+    }
+
+    /// Replaces every dns override with one address per server. See
+    /// [`add_dns_override`](Self::add_dns_override).
     pub fn set_dns_overrides(mut self, overrides: HashMap<String, String>) -> Self {
         tracing::trace!("Adding to dns_overrides: {:?}", &overrides);
-        self.dns_overrides = overrides;
+        self.dns_overrides = overrides
+            .into_iter()
+            .map(|(server, address)| (server, vec![address]))
+            .collect();
         self
     }
 
+    /// Sets the PEM file holding the client certificate and its private key.
     pub fn set_client_certificate(mut self, certificate_path: &str) -> Self {
-        self.client_certificate_path = Some(certificate_path.to_string());
+        self.client_certificate = Some(ClientCertificate::PemFile(certificate_path.into()));
+        self
+    }
+
+    /// Sets the client certificate and its private key from PEM encoded bytes, such as a secret
+    /// read from a vault.
+    pub fn set_client_certificate_pem<B: Into<Vec<u8>>>(mut self, pem: B) -> Self {
+        self.client_certificate = Some(ClientCertificate::Pem(pem.into()));
+        self
+    }
+
+    /// Sets the PFX (PKCS#12) file holding the client certificate, and the password it is
+    /// protected with.
+    pub fn set_client_certificate_pfx<P: Into<PathBuf>>(mut self, path: P, password: &str) -> Self {
+        self.client_certificate = Some(ClientCertificate::PfxFile {
+            path: path.into(),
+            password: password.to_string(),
+        });
+        self
+    }
+
+    /// Sets the client certificate from the bytes of a PFX (PKCS#12) file, and the password it
+    /// is protected with.
+    pub fn set_client_certificate_pfx_bytes<B: Into<Vec<u8>>>(
+        mut self,
+        bytes: B,
+        password: &str,
+    ) -> Self {
+        self.client_certificate = Some(ClientCertificate::Pfx {
+            bytes: bytes.into(),
+            password: password.to_string(),
+        });
+        self
+    }
+
+    /// Trusts the CA certificates of a PEM bundle file, such as the CA of a self-signed cluster,
+    /// on top of the public CAs. May be called more than once.
+    pub fn add_trusted_ca_certificates<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.trusted_ca_certificates
+            .push(TrustedCaCertificates::PemFile(path.into()));
+        self
+    }
+
+    /// Trusts the CA certificates of a PEM encoded bundle on top of the public CAs. May be called
+    /// more than once.
+    pub fn add_trusted_ca_certificates_pem<B: Into<Vec<u8>>>(mut self, pem: B) -> Self {
+        self.trusted_ca_certificates
+            .push(TrustedCaCertificates::Pem(pem.into()));
+        self
+    }
+
+    /// Only accepts server certificates with one of the pinned SHA-1 thumbprints, hex encoded
+    /// like RavenDB Studio shows them. The certificates must still be valid and trusted.
+    pub fn add_pinned_server_thumbprint(mut self, thumbprint: &str) -> Self {
+        self.pinned_server_thumbprints.push(thumbprint.to_string());
+        self
+    }
+
+    pub fn set_conventions(mut self, conventions: DocumentConventions) -> Self {
+        self.conventions = conventions;
+        self
+    }
+
+    /// Allows plain http urls along with a client certificate, such as for a local test server.
+    /// The certificate is then never sent.
+    pub fn set_allow_http_with_certificate(mut self, allow: bool) -> Self {
+        self.allow_http_with_certificate = allow;
         self
     }
 
+    /// Sends all requests through the proxy at `proxy_address`. Use
+    /// [`set_proxy`](Self::set_proxy) to proxy only some schemes, authenticate, or leave hosts
+    /// out.
     pub fn set_proxy_address(mut self, proxy_address: &str) -> Self {
-        self.proxy_address = Some(proxy_address.to_string());
+        self.proxy = Some(ProxySettings::new(proxy_address));
+        self
+    }
+
+    pub fn set_proxy(mut self, proxy: ProxySettings) -> Self {
+        self.proxy = Some(proxy);
         self
     }
 
@@ -73,18 +265,25 @@ impl DocumentStoreBuilder {
         // Validate URLS
         let initial_urls = validate_urls(
             self.document_store_urls.as_slice(),
-            self.client_certificate_path.is_some(),
+            self.client_certificate.is_some(),
+            self.allow_http_with_certificate,
         )?;
 
         // Parse dns overrides
         let dns_overrides = self
             .dns_overrides
             .iter()
-            .map(|(server, ip)| {
-                let ip: IpAddr = ip.parse()?;
-                Ok((server.clone(), ip))
+            .map(|(server, addresses)| {
+                let addresses = parse_dns_override(addresses).map_err(|reason| {
+                    tracing::error!("Invalid dns override for `{}`: {}", server, reason);
+                    DocumentStoreError::InvalidDnsOverride {
+                        host: server.clone(),
+                        reason,
+                    }
+                })?;
+                Ok((server.clone(), addresses))
             })
-            .collect::<Result<DnsOverrides, anyhow::Error>>()?;
+            .collect::<Result<DnsOverrides, DocumentStoreError>>()?;
 
         // let topology_info = ClusterTopologyInfo {
         //     topology: ClusterTopology {
@@ -94,49 +293,60 @@ impl DocumentStoreBuilder {
         //     ..Default::default()
         // };
 
-        let identity = match &self.client_certificate_path {
-            Some(certpath) => {
-                // Open and validate certificate, and create an identity from it
-                let mut buf = Vec::new();
-                File::open(certpath)
-                    .map_err(|e| {
-                        let err =
-                            anyhow::anyhow!("Failed to open certificate file. Caused by: {}", e);
-                        tracing::error!("{}", &err);
-                        err
-                    })?
-                    .read_to_end(&mut buf)
-                    .map_err(|e| {
-                        let err =
-                            anyhow::anyhow!("File was opened but unable to read. Caused by: {}", e);
-                        tracing::error!("{}", err);
-                        err
-                    })?;
-                let id = reqwest::Identity::from_pem(&buf).map_err(|e| {
-                    let err = anyhow::anyhow!("Invalid pem file. Caused by: {}", e);
-                    tracing::error!("{}", err);
-                    err
-                })?;
-                Some(id)
-            }
-            None => None,
-        };
+        // Open and validate certificate
+        let client_certificate_pem = self
+            .client_certificate
+            .as_ref()
+            .map(ClientCertificate::to_pem)
+            .transpose()?;
+
+        // Ensure the proxy settings are usable before every executor's client relies on them
+        if let Some(proxy) = &self.proxy {
+            proxy.to_reqwest_proxy().map_err(|e| {
+                tracing::error!("Invalid proxy settings. Caused by: {:?}", e);
+                DocumentStoreError::InvalidProxy(e)
+            })?;
+        }
+
+        // Read the trusted CAs and parse the pinned thumbprints
+        let trust_store = self.trust_store().map_err(|e| {
+            tracing::error!("Invalid trust store. Caused by: {:?}", e);
+            DocumentStoreError::InvalidTrustStore(e)
+        })?;
 
         // Create an initial configuration for the DocumentStoreActor
         let initial_config = DocumentStoreInitialConfiguration {
             //async_document_id_generator: self.async_document_id_generator.clone(),
-            client_identity: identity,
+            conventions: self.conventions.clone(),
             // cluster_topology: topology_info,
-            initial_urls: initial_urls.values().cloned().collect::<Vec<_>>(),
+            initial_urls,
             database_name: self.database_name.clone(),
-            dns_overrides,
-            proxy_address: self.proxy_address.clone(),
+            http_client_settings: HttpClientSettings {
+                client_certificate_pem,
+                dns_overrides,
+                proxy: self.proxy.clone(),
+                trust_store,
+            },
         };
 
         tracing::trace!("Initial Configuration: {:?}", &initial_config);
 
         Ok(DocumentStore::new(initial_config))
     }
+
+    fn trust_store(&self) -> anyhow::Result<TrustStore> {
+        let ca_bundles = self
+            .trusted_ca_certificates
+            .iter()
+            .map(|certificates| match certificates {
+                TrustedCaCertificates::PemFile(path) => std::fs::read(path).with_context(|| {
+                    format!("Failed to read CA certificates file `{}`", path.display())
+                }),
+                TrustedCaCertificates::Pem(pem) => Ok(pem.clone()),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        TrustStore::new(&ca_bundles, &self.pinned_server_thumbprints)
+    }
 }
 
 #[allow(clippy::derivable_impls)] //TODO: Remove this allow when ready
@@ -146,101 +356,202 @@ impl Default for DocumentStoreBuilder {
 
         Self {
             //async_document_id_generator: Box::new(AsyncMultiDatabaseHiLoIdGenerator::default()),
-            client_certificate_path: None,
+            client_certificate: None,
+            conventions: DocumentConventions::default(),
             database_name: None,
             dns_overrides: HashMap::default(),
             document_store_urls: Vec::new(),
-            proxy_address: None,
+            pinned_server_thumbprints: Vec::new(),
+            proxy: None,
+            trusted_ca_certificates: Vec::new(),
+            allow_http_with_certificate: false,
         }
     }
 }
 
-/// Converts the provided URL strings to a [`Vec`] of [`Url`], ensuring they are a valid format.
+/// Reads an environment variable through `lookup`, returning `None` if it is unset or empty.
+fn env_var<F>(lookup: F, name: &str) -> Result<Option<String>, DocumentStoreError>
+where
+    F: Fn(&str) -> Result<String, std::env::VarError>,
+{
+    match lookup(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value.trim().to_string())),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(anyhow::Error::new(e)
+            .context(format!("Unable to read environment variable `{}`", name))
+            .into()),
+    }
+}
+
+/// Parses the addresses of a dns override, returning the reason they can't be used if they
+/// aren't IP addresses with an optional port, or don't share their port.
+fn parse_dns_override(addresses: &[String]) -> Result<Vec<SocketAddr>, String> {
+    let addresses = addresses
+        .iter()
+        .map(|address| {
+            let address = address.trim();
+            address
+                .parse::<SocketAddr>()
+                .or_else(|_| address.parse::<IpAddr>().map(|ip| SocketAddr::new(ip, 0)))
+                .map_err(|_| format!("`{}` is not an IP address with an optional port", address))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let Some(first) = addresses.first() else {
+        return Err("No address was given".to_string());
+    };
+    if addresses
+        .iter()
+        .any(|address| address.port() != first.port())
+    {
+        return Err("The addresses must share a port".to_string());
+    }
+    Ok(addresses)
+}
+
+/// Converts the provided URL strings to a [`Vec`] of [`Url`] in the same order, ensuring each is
+/// the root of a server.
 ///
-/// Also ensures all provided URL strings use the same schema: either https or http, but never both within the
-/// list.
+/// Also ensures all provided URL strings use the same scheme and point at different servers.
+/// https is required with a client certificate and http without one, unless
+/// `allow_http_with_certificate` is set.
 #[instrument(level = "debug", name = "Validate URLs")]
-fn validate_urls<T: std::fmt::Debug>(
+fn validate_urls<T>(
     urls: &[T],
-    require_https: bool,
-) -> anyhow::Result<HashMap<String, Url>>
+    has_certificate: bool,
+    allow_http_with_certificate: bool,
+) -> Result<Vec<Url>, DocumentStoreError>
 where
-    T: AsRef<str>,
+    T: AsRef<str> + std::fmt::Debug,
 {
-    //let mut clean_urls = Vec::new();
-
-    //TODO: Check URLs are valid
-    //TODO: Check all URLs are either http OR https, no mixing
+    let mut clean_urls: Vec<Url> = Vec::with_capacity(urls.len());
+    let mut errors = Vec::new();
+
+    for url in urls {
+        match validate_url(
+            url.as_ref(),
+            &clean_urls,
+            has_certificate,
+            allow_http_with_certificate,
+        ) {
+            Ok(clean_url) => clean_urls.push(clean_url),
+            Err(reason) => {
+                tracing::error!("Invalid url `{}`: {}", url.as_ref(), reason);
+                errors.push(InvalidUrl {
+                    url: url.as_ref().to_string(),
+                    reason,
+                });
+            }
+        }
+    }
 
-    let clean_urls = urls
-        .iter()
-        .flat_map(|url| -> anyhow::Result<Url> { Ok(Url::parse(url.as_ref())?) })
-        .map(|url| (url.to_string(), url))
-        .collect::<HashMap<_, _>>();
+    if !errors.is_empty() {
+        return Err(DocumentStoreError::InvalidUrls(errors));
+    }
+    Ok(clean_urls)
+}
 
-    let desired_scheme = if require_https { "https" } else { "http" };
+/// Returns the reason `url` can't be used alongside the already validated `clean_urls`.
+fn validate_url(
+    url: &str,
+    clean_urls: &[Url],
+    has_certificate: bool,
+    allow_http_with_certificate: bool,
+) -> Result<Url, String> {
+    let parsed = Url::parse(url.trim()).map_err(|e| format!("Not a valid url: {}", e))?;
+
+    match (parsed.scheme(), has_certificate) {
+        ("https", true) | ("http", false) => {}
+        ("http", true) if allow_http_with_certificate => {}
+        ("http", true) => {
+            return Err(
+                "http urls can't be used with a client certificate unless explicitly allowed"
+                    .to_string(),
+            )
+        }
+        ("https", false) => return Err("https urls need a client certificate".to_string()),
+        (scheme, _) => return Err(format!("Unsupported scheme `{}`", scheme)),
+    }
+    let Some(host) = parsed.host_str() else {
+        return Err("The url has no host".to_string());
+    };
+    if parsed.path() != "/" {
+        return Err(format!(
+            "The url must be the root of the server, not `{}`",
+            parsed.path()
+        ));
+    }
+    if parsed.query().is_some() || parsed.fragment().is_some() {
+        return Err("The url must not have a query or fragment".to_string());
+    }
+    if !parsed.username().is_empty() || parsed.password().is_some() {
+        return Err("The url must not hold credentials".to_string());
+    }
 
-    for url in clean_urls.values().collect::<Vec<_>>() {
-        if url.scheme() != desired_scheme {
-            return Err(anyhow::anyhow!("Url does not have correct scheme: {}", url));
+    for other in clean_urls {
+        if other.scheme() != parsed.scheme() {
+            return Err(format!("Mixes http and https with `{}`", other));
+        }
+        if other.host_str() == Some(host)
+            && other.port_or_known_default() == parsed.port_or_known_default()
+        {
+            return Err(format!("Points at the same server as `{}`", other));
         }
     }
 
-    Ok(clean_urls)
+    Ok(parsed)
 }
 
 #[cfg(test)]
 mod tests {
-    #![allow(non_snake_case)]
-    use std::collections::HashMap;
-
     use url::Url;
 
-    use crate::{DocumentStoreBuilder, DocumentStoreError};
+    use crate::{DocumentStoreBuilder, DocumentStoreError, ProxyMode, ProxySettings};
+
+    use std::{collections::HashMap, net::SocketAddr};
 
-    use super::validate_urls;
+    use super::{parse_dns_override, validate_urls};
 
     #[test]
-    fn validate_urls_returns_correct_HashMap_for_http_strings() {
+    fn validate_urls_keeps_order_of_http_strings() {
         // Arrange
-        let mut baseline_urls = HashMap::<String, Url>::new();
-        baseline_urls.insert(
-            "http://starwars.com/".to_string(),
-            Url::parse("http://starwars.com").unwrap(),
-        );
-        baseline_urls.insert(
-            "http://google.com/".to_string(),
-            Url::parse("http://google.com").unwrap(),
-        );
-
-        let urls = vec!["http://starwars.com", "http://google.com"];
+        let urls = vec![
+            "http://starwars.com",
+            "http://google.com/",
+            "http://google.com:8080",
+        ];
 
         // Act
-        let result = validate_urls(urls.as_slice(), false).unwrap();
+        let result = validate_urls(urls.as_slice(), false, false).unwrap();
+
         // Assert
-        assert_eq!(result, baseline_urls);
+        assert_eq!(
+            result,
+            vec![
+                Url::parse("http://starwars.com").unwrap(),
+                Url::parse("http://google.com").unwrap(),
+                Url::parse("http://google.com:8080").unwrap(),
+            ]
+        );
     }
 
     #[test]
-    fn validate_urls_returns_correct_HashMap_for_https_strings() {
+    fn validate_urls_returns_https_strings_with_certificate() {
         // Arrange
-        let mut baseline_urls = HashMap::<String, Url>::new();
-        baseline_urls.insert(
-            "https://starwars.com/".to_string(),
-            Url::parse("https://starwars.com").unwrap(),
-        );
-        baseline_urls.insert(
-            "https://google.com/".to_string(),
-            Url::parse("https://google.com").unwrap(),
-        );
-
         let urls = vec!["https://starwars.com", "https://google.com"];
 
         // Act
-        let result = validate_urls(urls.as_slice(), true).unwrap();
+        let result = validate_urls(urls.as_slice(), true, false).unwrap();
 
         // Assert
-        assert_eq!(result, baseline_urls);
+        assert_eq!(
+            result,
+            vec![
+                Url::parse("https://starwars.com").unwrap(),
+                Url::parse("https://google.com").unwrap(),
+            ]
+        );
     }
 
     #[test]
@@ -249,8 +560,75 @@ mod tests {
         let urls = vec!["https://starwars.com", "http://google.com"];
 
         // Assert
-        assert!(validate_urls(urls.as_slice(), true).is_err());
-        assert!(validate_urls(urls.as_slice(), false).is_err());
+        assert!(validate_urls(urls.as_slice(), true, false).is_err());
+        assert!(validate_urls(urls.as_slice(), false, false).is_err());
+        assert!(validate_urls(urls.as_slice(), true, true).is_err());
+    }
+
+    #[test]
+    fn validate_urls_fails_for_every_bad_entry() {
+        let bad_urls = [
+            "http//starwars.com",
+            "http://starwars.com/databases",
+            "http://starwars.com?database=Orders",
+            "ftp://starwars.com",
+        ];
+
+        for bad_url in bad_urls {
+            let result = validate_urls(&["http://google.com", bad_url], false, false);
+
+            assert!(
+                matches!(&result, Err(DocumentStoreError::InvalidUrls(errors)) if errors[0].url == bad_url),
+                "{} was accepted",
+                bad_url
+            );
+        }
+    }
+
+    #[test]
+    fn validate_urls_fails_for_duplicate_hosts() {
+        let urls = ["http://starwars.com", "http://STARWARS.com:80/"];
+
+        assert!(matches!(
+            validate_urls(&urls, false, false),
+            Err(DocumentStoreError::InvalidUrls(_))
+        ));
+    }
+
+    #[test]
+    fn validate_urls_reports_every_bad_url() {
+        // Arrange
+        let urls = [
+            "http://starwars.com",
+            "http//google.com",
+            "https://starwars.com:8080",
+        ];
+
+        // Act
+        let result = validate_urls(&urls, false, false);
+
+        // Assert
+        match result {
+            Err(DocumentStoreError::InvalidUrls(errors)) => {
+                let bad_urls = errors
+                    .iter()
+                    .map(|error| error.url.as_str())
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    bad_urls,
+                    vec!["http//google.com", "https://starwars.com:8080"]
+                );
+            }
+            _ => panic!("expected both bad urls to be reported"),
+        }
+    }
+
+    #[test]
+    fn validate_urls_allows_http_with_certificate_only_when_opted_in() {
+        let urls = ["http://localhost:8080"];
+
+        assert!(validate_urls(&urls, true, false).is_err());
+        assert!(validate_urls(&urls, true, true).is_ok());
     }
 
     #[tokio::test]
@@ -282,6 +660,145 @@ mod tests {
         assert!(document_store.is_err());
     }
 
+    #[tokio::test]
+    async fn documentstorebuilder_build_succeeds_for_pfx_bytes() {
+        // Arrange
+        let urls = ["https://localhost:8080"];
+        let pfx = std::fs::read("../ravendb-client_dev_cert.pfx").unwrap();
+
+        let document_store = DocumentStoreBuilder::new()
+            .set_client_certificate_pfx_bytes(pfx, "ravendb")
+            .set_urls(&urls)
+            .build();
+
+        // Assert
+        assert!(document_store.is_ok());
+    }
+
+    #[tokio::test]
+    async fn documentstorebuilder_build_fails_for_invalid_pinned_thumbprint() {
+        // Arrange
+        let urls = ["https://localhost:8080"];
+
+        let document_store = DocumentStoreBuilder::new()
+            .set_client_certificate("../ravendb-client_dev_cert.pem")
+            .add_trusted_ca_certificates("../ravendb-client_dev_cert.pem")
+            .add_pinned_server_thumbprint("not a thumbprint")
+            .set_urls(&urls)
+            .build();
+
+        // Assert
+        assert!(matches!(
+            document_store,
+            Err(DocumentStoreError::InvalidTrustStore(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn documentstorebuilder_from_connection_string_builds() {
+        // Arrange
+        let connection_string = "Url=https://a.example.com,https://b.example.com;Database=Orders;\
+            Certificate=../ravendb-client_dev_cert.pfx;Password=ravendb";
+
+        // Act
+        let document_store = DocumentStoreBuilder::from_connection_string(connection_string)
+            .unwrap()
+            .build();
+
+        // Assert
+        assert!(document_store.is_ok());
+    }
+
+    #[tokio::test]
+    async fn documentstorebuilder_from_env_reads_raven_variables() {
+        // Arrange
+        let variables = HashMap::from([
+            ("RAVEN_URLS", "https://a.example.com, https://b.example.com"),
+            ("RAVEN_DATABASE", "Orders"),
+            ("RAVEN_CERT", "../ravendb-client_dev_cert.pem"),
+            ("RAVEN_PROXY", " "),
+        ]);
+
+        // Act
+        let builder = DocumentStoreBuilder::from_env_with(|name| {
+            variables
+                .get(name)
+                .map(|value| value.to_string())
+                .ok_or(std::env::VarError::NotPresent)
+        })
+        .unwrap();
+
+        // Assert
+        assert_eq!(
+            builder.document_store_urls,
+            vec!["https://a.example.com", "https://b.example.com"]
+        );
+        assert_eq!(builder.database_name.as_deref(), Some("Orders"));
+        assert!(builder.proxy.is_none());
+        assert!(builder.build().is_ok());
+    }
+
+    #[test]
+    fn parse_dns_override_accepts_ports_and_several_addresses() {
+        // Arrange
+        let addresses = vec!["127.0.0.1:8081".to_string(), "[::1]:8081".to_string()];
+
+        // Act
+        let result = parse_dns_override(&addresses).unwrap();
+
+        // Assert
+        assert_eq!(
+            result,
+            vec![
+                "127.0.0.1:8081".parse::<SocketAddr>().unwrap(),
+                "[::1]:8081".parse().unwrap()
+            ]
+        );
+        assert_eq!(
+            parse_dns_override(&["10.0.0.1".to_string()]).unwrap(),
+            vec!["10.0.0.1:0".parse::<SocketAddr>().unwrap()]
+        );
+    }
+
+    #[tokio::test]
+    async fn documentstorebuilder_build_fails_for_invalid_dns_override() {
+        // Arrange
+        let urls = ["http://raven1:8080"];
+
+        let document_store = DocumentStoreBuilder::new()
+            .add_dns_override("raven1", "127.0.0.1:8081")
+            .add_dns_override("raven1", "127.0.0.2:8082")
+            .set_urls(&urls)
+            .build();
+
+        // Assert
+        assert!(matches!(
+            document_store,
+            Err(DocumentStoreError::InvalidDnsOverride { host, .. }) if host == "raven1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn documentstorebuilder_build_fails_for_invalid_proxy() {
+        // Arrange
+        let urls = ["http://localhost:8080"];
+
+        let document_store = DocumentStoreBuilder::new()
+            .set_proxy(
+                ProxySettings::new("not a proxy")
+                    .set_mode(ProxyMode::Https)
+                    .set_basic_auth("app", "secret"),
+            )
+            .set_urls(&urls)
+            .build();
+
+        // Assert
+        assert!(matches!(
+            document_store,
+            Err(DocumentStoreError::InvalidProxy(_))
+        ));
+    }
+
     #[tokio::test]
     async fn documentstorebuilder_build_fails_if_no_urls() {
         let document_store = DocumentStoreBuilder::new()
@@ -0,0 +1,169 @@
+use std::{collections::HashMap, fmt, path::PathBuf};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{ClientCertificate, DocumentConventions, ProxySettings};
+
+/// Everything a [`DocumentStoreBuilder`](crate::DocumentStoreBuilder) can be set up with, in a
+/// form that can be read from TOML, YAML or JSON files with serde.
+///
+/// Every field is optional. It is validated by
+/// [`DocumentStoreBuilder::build`](crate::DocumentStoreBuilder::build) like settings set by hand.
+///
+/// ```toml
+/// urls = ["https://a.example.com", "https://b.example.com"]
+/// database = "Orders"
+/// certificate = { path = "/etc/raven/app.pfx", password = "secret" }
+/// dns_overrides = { "a.example.com" = "10.0.0.1", "b.example.com" = ["10.0.0.2:8081"] }
+///
+/// [conventions]
+/// request_timeout = 30
+/// read_balance_behavior = "RoundRobin"
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DocumentStoreConfig {
+    pub urls: Vec<String>,
+    pub database: Option<String>,
+    pub certificate: Option<CertificateConfig>,
+    /// The address, or list of addresses, of each overridden host. See
+    /// [`DocumentStoreBuilder::add_dns_override`](crate::DocumentStoreBuilder::add_dns_override).
+    #[serde(deserialize_with = "deserialize_dns_overrides")]
+    pub dns_overrides: HashMap<String, Vec<String>>,
+    pub proxy: Option<ProxySettings>,
+    pub conventions: DocumentConventions,
+    /// See [`DocumentStoreBuilder::set_allow_http_with_certificate`](crate::DocumentStoreBuilder::set_allow_http_with_certificate).
+    pub allow_http_with_certificate: bool,
+}
+
+fn deserialize_dns_overrides<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Addresses {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    let overrides = HashMap::<String, Addresses>::deserialize(deserializer)?;
+    Ok(overrides
+        .into_iter()
+        .map(|(host, addresses)| match addresses {
+            Addresses::One(address) => (host, vec![address]),
+            Addresses::Many(addresses) => (host, addresses),
+        })
+        .collect())
+}
+
+/// Where the client certificate is read from.
+#[derive(Clone, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum CertificateConfig {
+    /// A PEM or PFX file, read like the `Certificate` of
+    /// [`DocumentStoreBuilder::from_connection_string`](crate::DocumentStoreBuilder::from_connection_string).
+    File {
+        path: PathBuf,
+        password: Option<String>,
+    },
+    /// The PEM encoded certificate and private key.
+    Pem { pem: String },
+}
+
+impl From<CertificateConfig> for ClientCertificate {
+    fn from(config: CertificateConfig) -> Self {
+        match config {
+            CertificateConfig::File { path, password } => {
+                ClientCertificate::from_path(path, password)
+            }
+            CertificateConfig::Pem { pem } => ClientCertificate::Pem(pem.into_bytes()),
+        }
+    }
+}
+
+// Keeps the key material and passwords out of logs
+impl fmt::Debug for CertificateConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CertificateConfig::File { path, .. } => f
+                .debug_struct("File")
+                .field("path", path)
+                .finish_non_exhaustive(),
+            CertificateConfig::Pem { .. } => f.write_str("Pem { .. }"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{DocumentStoreBuilder, ReadBalanceBehavior};
+
+    use super::{CertificateConfig, DocumentStoreConfig};
+
+    #[test]
+    fn document_store_config_deserializes_every_setting() {
+        // Arrange
+        let json = serde_json::json!({
+            "urls": ["https://a.example.com"],
+            "database": "Orders",
+            "certificate": { "path": "/etc/raven/app.pfx", "password": "secret" },
+            "dns_overrides": {
+                "a.example.com": "10.0.0.1",
+                "b.example.com": ["10.0.0.2:8081", "10.0.0.3:8081"]
+            },
+            "proxy": "http://proxy:3128",
+            "conventions": { "request_timeout": 1.5, "read_balance_behavior": "RoundRobin" }
+        });
+
+        // Act
+        let config: DocumentStoreConfig = serde_json::from_value(json).unwrap();
+
+        // Assert
+        assert_eq!(config.urls, vec!["https://a.example.com"]);
+        assert_eq!(config.database.as_deref(), Some("Orders"));
+        assert!(matches!(
+            config.certificate,
+            Some(CertificateConfig::File {
+                password: Some(_),
+                ..
+            })
+        ));
+        assert_eq!(config.dns_overrides["a.example.com"], vec!["10.0.0.1"]);
+        assert_eq!(config.dns_overrides["b.example.com"].len(), 2);
+        assert_eq!(config.proxy.unwrap().address(), "http://proxy:3128");
+        assert_eq!(
+            config.conventions.request_timeout(),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(
+            config.conventions.read_balance_behavior(),
+            ReadBalanceBehavior::RoundRobin
+        );
+        assert!(config.conventions.use_compression());
+    }
+
+    #[test]
+    fn document_store_config_rejects_unknown_fields() {
+        let result = serde_json::from_str::<DocumentStoreConfig>(r#"{ "url": "http://a" }"#);
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn from_config_builds() {
+        // Arrange
+        let config: DocumentStoreConfig = serde_json::from_value(serde_json::json!({
+            "urls": ["https://localhost:8080"],
+            "certificate": { "path": "../ravendb-client_dev_cert.pem" }
+        }))
+        .unwrap();
+
+        // Act
+        let document_store = DocumentStoreBuilder::from_config(config).build();
+
+        // Assert
+        assert!(document_store.is_ok());
+    }
+}
@@ -1,10 +1,25 @@
-use crate::error_chain_fmt;
+use crate::{error_chain_fmt, RequestExecutorError};
 
 #[derive(thiserror::Error)]
 pub enum DocumentStoreError {
+    #[error("The client certificate could not be loaded")]
+    InvalidClientCertificate(#[source] anyhow::Error),
+    #[error("The connection string is invalid: {0}")]
+    InvalidConnectionString(String),
+    #[error("Invalid dns override for `{host}`: {reason}")]
+    InvalidDnsOverride { host: String, reason: String },
+    /// Every url that can't be used, along with the reason why.
+    #[error("Invalid urls: {}", format_url_errors(.0))]
+    InvalidUrls(Vec<InvalidUrl>),
+    #[error("The proxy settings are invalid")]
+    InvalidProxy(#[source] anyhow::Error),
+    #[error("The trusted CA certificates or pinned server thumbprints are invalid")]
+    InvalidTrustStore(#[source] anyhow::Error),
     #[error("No URLs were supplied and a document store can't exist without at least one")]
     MissingUrlsError,
     #[error(transparent)]
+    RequestExecutorError(#[from] RequestExecutorError),
+    #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
 impl std::fmt::Debug for DocumentStoreError {
@@ -12,3 +27,19 @@ impl std::fmt::Debug for DocumentStoreError {
         error_chain_fmt(self, f)
     }
 }
+
+/// A url that was rejected, see [`DocumentStoreError::InvalidUrls`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidUrl {
+    /// The url as it was given.
+    pub url: String,
+    pub reason: String,
+}
+
+fn format_url_errors(errors: &[InvalidUrl]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("`{}`: {}", error.url, error.reason))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,12 +1,16 @@
+use std::{sync::Arc, time::Duration};
+
 use anyhow::Context;
 use reqwest::Url;
 use tokio::sync::{mpsc, oneshot};
 use tracing::instrument;
 
+use super::hilo_id_generator::HiLoIdGenerator;
 use crate::{
-    request_executor::RequestExecutor, run_document_store_actor, DocumentSession,
-    DocumentStoreActor, DocumentStoreBuilder, DocumentStoreError,
-    DocumentStoreInitialConfiguration, DocumentStoreMessage,
+    request_executor::RequestExecutor, run_document_store_actor, AggressiveCacheGuard,
+    ClientCertificate, DocumentConventions, DocumentSession, DocumentStoreActor,
+    DocumentStoreBuilder, DocumentStoreError, DocumentStoreInitialConfiguration,
+    DocumentStoreMessage,
 };
 
 /**
@@ -31,6 +35,9 @@ TODO: Uncomment this example after build function is completed
 */
 #[derive(Clone, Debug)]
 pub struct DocumentStore {
+    /// Shared with the actor's executors by value, so they can't change once the store exists.
+    conventions: Arc<DocumentConventions>,
+    hilo_id_generator: Arc<HiLoIdGenerator>,
     sender: mpsc::Sender<DocumentStoreMessage>,
 }
 
@@ -44,10 +51,15 @@ impl DocumentStore {
     // This is pub(crate) so only the builder can crank it out
     pub(crate) fn new(initial_config: DocumentStoreInitialConfiguration) -> Self {
         let (sender, receiver) = mpsc::channel(8);
+        let conventions = Arc::new(initial_config.conventions.clone());
         let actor = DocumentStoreActor::new(receiver, initial_config);
         tokio::spawn(run_document_store_actor(actor));
 
-        Self { sender }
+        Self {
+            conventions,
+            hilo_id_generator: Arc::default(),
+            sender,
+        }
     }
 
     // #[instrument(
@@ -75,6 +87,11 @@ impl DocumentStore {
     //     rx.await?.context("DocumentStoreActor task has been killed")
     // }
 
+    /// Returns the conventions the store was built with. They are frozen once the store exists.
+    pub fn conventions(&self) -> &DocumentConventions {
+        &self.conventions
+    }
+
     #[instrument(
         level = "debug",
         name = "Actor Handle - Get Server Address",
@@ -90,6 +107,57 @@ impl DocumentStore {
         rx.await?.context("DocumentStoreActor task has been killed")
     }
 
+    /// Serves GET requests to the store's database from the HTTP cache without asking the
+    /// server, for as long as the returned guard is alive.
+    ///
+    /// Cached responses are dropped as soon as the server reports a change to any document or
+    /// index. See [`RequestExecutor::aggressively_cache_for`].
+    pub async fn aggressively_cache_for(
+        &self,
+        duration: Duration,
+    ) -> Result<AggressiveCacheGuard, anyhow::Error> {
+        let executor = self.get_request_executor(None).await?;
+        Ok(executor.aggressively_cache_for(duration).await)
+    }
+
+    /// Makes every request sent from now on present `certificate`, such as a rotated one,
+    /// keeping the request executors along with their topologies and caches.
+    ///
+    /// Requests already sent finish with the previous certificate. Returns
+    /// [`DocumentStoreError::InvalidClientCertificate`] and keeps the previous certificate if
+    /// the new one can't be loaded.
+    pub async fn replace_client_certificate(
+        &self,
+        certificate: ClientCertificate,
+    ) -> Result<(), DocumentStoreError> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(DocumentStoreMessage::ReplaceClientCertificate {
+                certificate,
+                respond_to: tx,
+            })
+            .await;
+        rx.await
+            .context("DocumentStoreActor task has been killed")?
+    }
+
+    /// Generates the id of a new document of the collection, such as `orders/1-A`, from a range
+    /// of ids reserved on the store's database.
+    pub(crate) async fn generate_document_id(
+        &self,
+        collection_name: &str,
+    ) -> anyhow::Result<String> {
+        let executor = self.get_request_executor(None).await?;
+        // The server's client configuration may override the separator
+        let separator = executor.conventions().await?.identity_parts_separator();
+        let document_id = self
+            .hilo_id_generator
+            .generate_document_id(&executor, collection_name, separator)
+            .await?;
+        Ok(document_id)
+    }
+
     pub fn open_session(&self) -> Result<DocumentSession, DocumentStoreError> {
         let session = DocumentSession::new(self.clone());
         Ok(session)
@@ -116,3 +184,50 @@ impl DocumentStore {
         rx.await?.context("DocumentStoreActor task has been killed")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{ClientCertificate, DocumentStoreBuilder, DocumentStoreError};
+
+    #[tokio::test]
+    async fn replace_client_certificate_accepts_pfx() {
+        // Arrange
+        let document_store = DocumentStoreBuilder::new()
+            .set_client_certificate("../ravendb-client_dev_cert.pem")
+            .set_urls(&["https://localhost:8080"])
+            .build()
+            .unwrap();
+
+        // Act
+        let result = document_store
+            .replace_client_certificate(ClientCertificate::PfxFile {
+                path: "../ravendb-client_dev_cert.pfx".into(),
+                password: "ravendb".to_string(),
+            })
+            .await;
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn replace_client_certificate_fails_for_invalid_certificate() {
+        // Arrange
+        let document_store = DocumentStoreBuilder::new()
+            .set_client_certificate("../ravendb-client_dev_cert.pem")
+            .set_urls(&["https://localhost:8080"])
+            .build()
+            .unwrap();
+
+        // Act
+        let result = document_store
+            .replace_client_certificate(ClientCertificate::Pem(b"not a certificate".to_vec()))
+            .await;
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(DocumentStoreError::InvalidClientCertificate(_))
+        ));
+    }
+}
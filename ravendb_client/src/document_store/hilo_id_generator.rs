@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use tokio::sync::Mutex;
+
+use crate::{
+    raven_command::{HiLoResult, NextHiLoCommand},
+    RequestExecutor, RequestExecutorError,
+};
+
+/// Generates document ids such as `orders/1-A` on the client, from ranges of ids the server
+/// reserves for each collection. Shared by every session of a store, so ranges are used up
+/// before new ones are reserved.
+#[derive(Debug, Default)]
+pub(crate) struct HiLoIdGenerator {
+    /// The current range of each id prefix.
+    ranges: Mutex<HashMap<String, HiLoRange>>,
+}
+
+#[derive(Debug)]
+struct HiLoRange {
+    prefix: String,
+    server_tag: String,
+    /// The last id handed out.
+    current: i64,
+    high: i64,
+    last_size: i64,
+    last_range_at: Option<String>,
+}
+
+impl HiLoRange {
+    fn document_id(&self) -> String {
+        format!("{}{}-{}", self.prefix, self.current, self.server_tag)
+    }
+}
+
+impl From<HiLoResult> for HiLoRange {
+    fn from(result: HiLoResult) -> Self {
+        Self {
+            prefix: result.prefix,
+            server_tag: result.server_tag,
+            current: result.low,
+            high: result.high,
+            last_size: result.last_size,
+            last_range_at: result.last_range_at,
+        }
+    }
+}
+
+impl HiLoIdGenerator {
+    /// Returns the next id of the collection, reserving a new range through `executor` once
+    /// the current one is used up.
+    pub(crate) async fn generate_document_id(
+        &self,
+        executor: &RequestExecutor,
+        collection_name: &str,
+        identity_parts_separator: char,
+    ) -> Result<String, RequestExecutorError> {
+        let tag = document_id_prefix(collection_name);
+        // Held while reserving a range, so concurrent sessions don't reserve one each
+        let mut ranges = self.ranges.lock().await;
+
+        if let Some(range) = ranges
+            .get_mut(&tag)
+            .filter(|range| range.current < range.high)
+        {
+            range.current += 1;
+            return Ok(range.document_id());
+        }
+
+        let previous = ranges.get(&tag);
+        let command = NextHiLoCommand {
+            tag: tag.clone(),
+            last_batch_size: previous.map(|range| range.last_size).unwrap_or_default(),
+            last_range_at: previous.and_then(|range| range.last_range_at.clone()),
+            identity_parts_separator,
+            last_max: previous.map(|range| range.high).unwrap_or_default(),
+        };
+        let range = HiLoRange::from(executor.execute(command, None).await?);
+        tracing::debug!(
+            "Reserved ids {} to {} of `{}`",
+            range.current,
+            range.high,
+            tag
+        );
+
+        let document_id = range.document_id();
+        ranges.insert(tag, range);
+        Ok(document_id)
+    }
+}
+
+/// Turns a collection name into the prefix of its ids like the official clients: names with a
+/// single capital, such as `Orders`, are lowercased, while others such as `OrderLines` are kept.
+fn document_id_prefix(collection_name: &str) -> String {
+    let capitals = collection_name
+        .chars()
+        .filter(char::is_ascii_uppercase)
+        .count();
+    if capitals <= 1 {
+        collection_name.to_lowercase()
+    } else {
+        collection_name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{DocumentConventions, RequestExecutor};
+
+    use super::{document_id_prefix, HiLoIdGenerator};
+
+    #[test]
+    fn document_id_prefix_lowercases_single_word_collections() {
+        assert_eq!(document_id_prefix("Orders"), "orders");
+        assert_eq!(document_id_prefix("OrderLines"), "OrderLines");
+    }
+
+    #[tokio::test]
+    async fn generate_document_id_uses_up_the_range_before_reserving_another() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/databases/db/hilo/next"))
+            .and(query_param("lastMax", "0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Prefix": "orders/", "Low": 1, "High": 2, "LastSize": 2,
+                "ServerTag": "A", "LastRangeAt": "2024-01-01T00:00:00.0000000Z"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/databases/db/hilo/next"))
+            .and(query_param("lastMax", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Prefix": "orders/", "Low": 3, "High": 6, "LastSize": 4,
+                "ServerTag": "B", "LastRangeAt": "2024-01-01T00:00:01.0000000Z"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let executor = RequestExecutor::new_for_single_node_without_configuration_updates(
+            Url::parse(&server.uri()).unwrap(),
+            "db".to_string(),
+            Default::default(),
+            DocumentConventions::default(),
+        )
+        .unwrap();
+        let generator = HiLoIdGenerator::default();
+
+        // Act
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            ids.push(
+                generator
+                    .generate_document_id(&executor, "Orders", '/')
+                    .await
+                    .unwrap(),
+            );
+        }
+
+        // Assert
+        assert_eq!(ids, vec!["orders/1-A", "orders/2-A", "orders/3-B"]);
+    }
+}
@@ -32,31 +32,45 @@ When `session` is dropped, it'll close any open handles appropriately on its own
 mod document_conventions;
 mod document_session;
 mod document_store;
+mod proxy_settings;
+mod raven_entity;
 
+pub mod client_configuration;
 pub mod cluster_topology;
 pub mod database_topology;
 pub mod node_selector;
 pub mod raven_command;
-pub mod raven_command_generic;
 pub mod ravendb_error;
 mod request_executor;
-mod server_node;
+pub mod server_node;
 
-use std::{collections::HashMap, net::IpAddr};
+use std::{collections::HashMap, net::SocketAddr};
 
+pub use document_conventions::*;
 pub use document_session::*;
 pub use document_store::*;
+pub use proxy_settings::*;
+pub use raven_entity::*;
+pub use request_executor::{
+    AggressiveCacheGuard, HttpCacheStats, RequestExecutor, RequestExecutorError,
+};
 
-pub type DnsOverrides = HashMap<String, IpAddr>;
+/// Addresses to connect to in place of the ones DNS resolves a host to. Addresses with port 0
+/// keep the port of the url.
+///
+/// The addresses of one host must share their port: requests are sent to that port, whichever
+/// address the connection ends up using.
+pub type DnsOverrides = HashMap<String, Vec<SocketAddr>>;
 
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
     f: &mut std::fmt::Formatter<'_>,
 ) -> std::fmt::Result {
     writeln!(f, "{}\n", e)?;
-    let current = e.source();
+    let mut current = e.source();
     while let Some(cause) = current {
         writeln!(f, "Caused by:\n\t{}", cause)?;
+        current = cause.source();
     }
     Ok(())
 }
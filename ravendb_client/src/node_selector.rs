@@ -1,22 +1,22 @@
+//! Requirements for the NodeSelector
+//! 1. Maintain the following state:
+//!    a. Current topology
+//!    b. Number of failures per node
+//!    c. Speed of each node (used to determine fastest)
+//!    d. Fastest node
+//!    e. Default node to try if all nodes in the server have faults
+//! 2. Provide a speed test capability for all nodes
+//! 3. Return the fastest node
+//! 4. Return a specific node
+//! 5. Return a "preferred" node
+//! 6. Return the topology if requested
+
 use std::collections::HashMap;
 
 use rand::{seq::IteratorRandom, thread_rng};
 
 use crate::{database_topology::DatabaseTopology, server_node::ServerNode};
 
-///! Requirements for the NodeSelector
-/// 1. Maintain the following state:
-///    a. Current topology
-///    b. Number of failures per node
-///    c. Speed of each node (used to determine fastest)
-///    d. Fastest node
-///    e. Default node to try if all nodes in the server have faults
-/// 2. Provide a speed test capability for all nodes
-/// 3. Return the fastest node
-/// 4. Return a specific node
-/// 5. Return a "preferred" node
-/// 6. Return the topology if requested
-
 #[derive(Debug)]
 pub struct NodeSelector {
     /// Whether or not to run speed tests
@@ -39,28 +39,88 @@ impl NodeSelector {
     }
 
     /// Returns the fastest node available if one exists.
+    ///
+    /// Only nodes without failures and with a recorded response time are considered. Until at
+    /// least one such node has responded, the preferred node is returned.
     pub fn get_fastest_node(&self) -> Option<ServerNode> {
-        // TODO: actually return the fastest node
-        // For now just return the first node
+        let fastest = self
+            .ordered_nodes()
+            .into_iter()
+            .filter(|node| self.failures_for(node) == 0)
+            .filter_map(|node| {
+                self.node_response_speed_ms
+                    .get(node)
+                    .map(|speed| (node, *speed))
+            })
+            .min_by_key(|(_, speed)| *speed)
+            .map(|(node, _)| node.clone());
+
+        if fastest.is_some() {
+            return fastest;
+        }
+
         self.get_preferred_node()
     }
 
+    /// Records how long the given node took to respond to a request, replacing any previous
+    /// measurement for it. A node that responds has recovered, so its failures are cleared.
+    pub fn record_response_time(&mut self, node: &ServerNode, response_time_ms: u32) {
+        self.node_response_speed_ms
+            .insert(node.clone(), response_time_ms);
+        self.node_failures.remove(node);
+    }
+
+    /// Records that the given node failed to respond to a request.
+    pub fn record_failure(&mut self, node: &ServerNode) {
+        *self.node_failures.entry(node.clone()).or_default() += 1;
+    }
+
+    /// Returns the nodes to try a request on, in order: `first`, then the rest of the topology
+    /// starting with the nodes that failed the least.
+    pub fn get_failover_nodes(&self, first: &ServerNode) -> Vec<ServerNode> {
+        let mut others = self
+            .ordered_nodes()
+            .into_iter()
+            .filter(|node| *node != first)
+            .collect::<Vec<_>>();
+        // A stable sort keeps nodes with the same number of failures in topology order
+        others.sort_by_key(|node| self.failures_for(node));
+
+        std::iter::once(first.clone())
+            .chain(others.into_iter().cloned())
+            .collect()
+    }
+
     /// Returns a specific node for the given session id.
+    ///
+    /// The session id is mapped onto the topology's nodes in a stable order, so the same session
+    /// id lands on the same node for as long as the topology doesn't change. If that node has
+    /// failures recorded against it, the preferred node is returned instead.
     pub fn get_node_by_session_id(&self, session_id: i32) -> Option<ServerNode> {
-        // TODO: actually return the session_id node.
-        // For now just return preferred node
+        let nodes = self.ordered_nodes();
+        if nodes.is_empty() {
+            return None;
+        }
+
+        let index = session_id.unsigned_abs() as usize % nodes.len();
+        let node = nodes[index];
+
+        if self.failures_for(node) == 0 {
+            return Some(node.clone());
+        }
+
         self.get_preferred_node()
     }
 
     /// Returns the currently preferred node.
     /// Right now this looks for the first node with 0 failures and returns it.
-    /// On the off chance all nodes have failures, it returns
+    /// On the off chance all nodes have failures, it returns a random node.
     pub fn get_preferred_node(&self) -> Option<ServerNode> {
         let x = self
-            .node_failures
-            .iter()
-            .find(|(node, count)| **count == 0)
-            .map(|(node, count)| node.clone());
+            .ordered_nodes()
+            .into_iter()
+            .find(|node| self.failures_for(node) == 0)
+            .cloned();
 
         if x.is_some() {
             return x;
@@ -80,6 +140,29 @@ impl NodeSelector {
         self.get_preferred_node()
     }
 
+    /// Returns the topology's nodes sorted by cluster tag, then url.
+    ///
+    /// The topology stores its nodes in a [`HashSet`](std::collections::HashSet), whose iteration
+    /// order can change between two otherwise identical topologies. Anything that maps a number
+    /// onto a node needs this ordering instead.
+    fn ordered_nodes(&self) -> Vec<&ServerNode> {
+        let mut nodes = match &self.topology {
+            Some(topology) => topology.nodes.iter().collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        nodes.sort_by(|a, b| {
+            a.cluster_tag
+                .cmp(&b.cluster_tag)
+                .then_with(|| a.url.as_str().cmp(b.url.as_str()))
+        });
+        nodes
+    }
+
+    /// Returns the number of failures recorded against the given node.
+    fn failures_for(&self, node: &ServerNode) -> u32 {
+        self.node_failures.get(node).copied().unwrap_or_default()
+    }
+
     /// Returns a random node if all are faulted.
     fn select_random_node(&self) -> Option<ServerNode> {
         if let Some(topology) = &self.topology {
@@ -90,3 +173,141 @@ impl NodeSelector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use url::Url;
+
+    use crate::{database_topology::DatabaseTopology, server_node::ServerNode};
+
+    use super::NodeSelector;
+
+    fn node(tag: &str) -> ServerNode {
+        let mut node = ServerNode::new(
+            Url::parse(&format!("http://{}.example.com", tag.to_lowercase())).unwrap(),
+            "db".to_string(),
+        );
+        node.cluster_tag = tag.to_string();
+        node
+    }
+
+    fn selector(tags: &[&str]) -> NodeSelector {
+        let topology = DatabaseTopology {
+            nodes: tags.iter().map(|tag| node(tag)).collect::<HashSet<_>>(),
+            ..Default::default()
+        };
+        NodeSelector::new(Some(topology))
+    }
+
+    #[test]
+    fn get_node_by_session_id_is_stable_across_identical_topologies() {
+        // Arrange
+        let first = selector(&["A", "B", "C"]);
+        let second = selector(&["C", "A", "B"]);
+
+        // Act & Assert
+        for session_id in -10..10 {
+            assert_eq!(
+                first.get_node_by_session_id(session_id),
+                second.get_node_by_session_id(session_id)
+            );
+        }
+    }
+
+    #[test]
+    fn get_node_by_session_id_spreads_sessions_over_nodes() {
+        // Arrange
+        let selector = selector(&["A", "B", "C"]);
+
+        // Act
+        let tags = (0..3)
+            .map(|session_id| selector.get_node_by_session_id(session_id).unwrap())
+            .map(|node| node.cluster_tag)
+            .collect::<Vec<_>>();
+
+        // Assert
+        assert_eq!(tags, vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn get_node_by_session_id_falls_back_to_preferred_node_on_failure() {
+        // Arrange
+        let mut selector = selector(&["A", "B"]);
+        selector.node_failures.insert(node("B"), 1);
+
+        // Act
+        let result = selector.get_node_by_session_id(1).unwrap();
+
+        // Assert
+        assert_eq!(result.cluster_tag, "A");
+    }
+
+    #[test]
+    fn get_fastest_node_returns_node_with_lowest_response_time() {
+        // Arrange
+        let mut selector = selector(&["A", "B", "C"]);
+        selector.record_response_time(&node("A"), 40);
+        selector.record_response_time(&node("B"), 10);
+        selector.record_response_time(&node("C"), 25);
+
+        // Act
+        let result = selector.get_fastest_node().unwrap();
+
+        // Assert
+        assert_eq!(result.cluster_tag, "B");
+    }
+
+    #[test]
+    fn get_fastest_node_skips_failed_nodes() {
+        // Arrange
+        let mut selector = selector(&["A", "B"]);
+        selector.record_response_time(&node("A"), 40);
+        selector.record_response_time(&node("B"), 10);
+        selector.node_failures.insert(node("B"), 1);
+
+        // Act
+        let result = selector.get_fastest_node().unwrap();
+
+        // Assert
+        assert_eq!(result.cluster_tag, "A");
+    }
+
+    #[test]
+    fn get_failover_nodes_tries_failed_nodes_last() {
+        // Arrange
+        let mut selector = selector(&["A", "B", "C"]);
+        selector.record_failure(&node("A"));
+
+        // Act
+        let tags = selector
+            .get_failover_nodes(&node("C"))
+            .into_iter()
+            .map(|node| node.cluster_tag)
+            .collect::<Vec<_>>();
+
+        // Assert
+        assert_eq!(tags, vec!["C", "B", "A"]);
+    }
+
+    #[test]
+    fn record_response_time_clears_failures() {
+        // Arrange
+        let mut selector = selector(&["A", "B"]);
+        selector.record_failure(&node("A"));
+
+        // Act
+        selector.record_response_time(&node("A"), 10);
+
+        // Assert
+        assert_eq!(selector.get_preferred_node().unwrap().cluster_tag, "A");
+    }
+
+    #[test]
+    fn get_node_by_session_id_returns_none_without_topology() {
+        let selector = NodeSelector::new(None);
+
+        assert!(selector.get_node_by_session_id(1).is_none());
+    }
+}
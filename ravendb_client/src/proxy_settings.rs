@@ -0,0 +1,181 @@
+use std::fmt;
+
+use anyhow::Context;
+use serde::Deserialize;
+
+/// The proxy every request of a [`DocumentStore`](crate::DocumentStore) is sent through.
+///
+/// Deserializes from the proxy's address, or from a table of the settings below:
+///
+/// ```toml
+/// [proxy]
+/// address = "http://proxy.example.com:3128"
+/// mode = "Https"
+/// username = "app"
+/// password = "secret"
+/// no_proxy = ["localhost", "10.0.0.0/8"]
+/// ```
+#[derive(Clone, Deserialize)]
+#[serde(from = "ProxySettingsConfig")]
+pub struct ProxySettings {
+    address: String,
+    mode: ProxyMode,
+    credentials: Option<(String, String)>,
+    no_proxy: Vec<String>,
+}
+
+impl ProxySettings {
+    /// Proxies all traffic through `address`, such as `http://proxy.example.com:3128`.
+    pub fn new(address: &str) -> Self {
+        Self {
+            address: address.to_string(),
+            mode: ProxyMode::default(),
+            credentials: None,
+            no_proxy: Vec::new(),
+        }
+    }
+
+    pub fn set_mode(mut self, mode: ProxyMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Authenticates to the proxy with basic authentication, including when tunneling https
+    /// requests with `CONNECT`.
+    pub fn set_basic_auth(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Sends requests to `host` directly. Takes host names, which match their subdomains too,
+    /// IP addresses and CIDR blocks, like the `NO_PROXY` environment variable.
+    pub fn add_no_proxy(mut self, host: &str) -> Self {
+        self.no_proxy.push(host.to_string());
+        self
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn mode(&self) -> ProxyMode {
+        self.mode
+    }
+
+    pub fn no_proxy(&self) -> &[String] {
+        &self.no_proxy
+    }
+
+    pub(crate) fn to_reqwest_proxy(&self) -> anyhow::Result<reqwest::Proxy> {
+        let proxy = match self.mode {
+            ProxyMode::Http => reqwest::Proxy::http(&self.address),
+            ProxyMode::Https => reqwest::Proxy::https(&self.address),
+            ProxyMode::All => reqwest::Proxy::all(&self.address),
+        }
+        .with_context(|| format!("Invalid proxy address `{}`", self.address))?;
+
+        let proxy = match &self.credentials {
+            Some((username, password)) => proxy.basic_auth(username, password),
+            None => proxy,
+        };
+        Ok(proxy.no_proxy(reqwest::NoProxy::from_string(&self.no_proxy.join(","))))
+    }
+}
+
+// Keeps the proxy password out of logs
+impl fmt::Debug for ProxySettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxySettings")
+            .field("address", &self.address)
+            .field("mode", &self.mode)
+            .field(
+                "credentials",
+                &self
+                    .credentials
+                    .as_ref()
+                    .map(|(username, _)| (username, "..")),
+            )
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+/// Which requests are sent through the proxy, by the scheme of their url.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+pub enum ProxyMode {
+    Http,
+    Https,
+    #[default]
+    All,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProxySettingsConfig {
+    Address(String),
+    #[serde(rename_all = "snake_case")]
+    Settings {
+        address: String,
+        #[serde(default)]
+        mode: ProxyMode,
+        username: Option<String>,
+        #[serde(default)]
+        password: String,
+        #[serde(default)]
+        no_proxy: Vec<String>,
+    },
+}
+
+impl From<ProxySettingsConfig> for ProxySettings {
+    fn from(config: ProxySettingsConfig) -> Self {
+        match config {
+            ProxySettingsConfig::Address(address) => ProxySettings::new(&address),
+            ProxySettingsConfig::Settings {
+                address,
+                mode,
+                username,
+                password,
+                no_proxy,
+            } => ProxySettings {
+                address,
+                mode,
+                credentials: username.map(|username| (username, password)),
+                no_proxy,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProxyMode, ProxySettings};
+
+    #[test]
+    fn proxy_settings_deserializes_from_address_or_table() {
+        // Act
+        let address: ProxySettings = serde_json::from_str(r#""http://proxy:3128""#).unwrap();
+        let table: ProxySettings = serde_json::from_value(serde_json::json!({
+            "address": "http://proxy:3128",
+            "mode": "Https",
+            "username": "app",
+            "password": "secret",
+            "no_proxy": ["localhost"]
+        }))
+        .unwrap();
+
+        // Assert
+        assert_eq!(address.mode(), ProxyMode::All);
+        assert_eq!(table.mode(), ProxyMode::Https);
+        assert_eq!(table.no_proxy(), ["localhost"]);
+        assert!(!format!("{:?}", table).contains("secret"));
+    }
+
+    #[test]
+    fn to_reqwest_proxy_rejects_invalid_address() {
+        let result = ProxySettings::new("not a proxy")
+            .set_basic_auth("app", "secret")
+            .to_reqwest_proxy();
+
+        assert!(result.is_err());
+    }
+}
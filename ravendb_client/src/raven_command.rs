@@ -1,111 +1,125 @@
-//! The raven commands will be the only way to directly interact with the server
-/// Although a command may exist that lets you artitrarily send a REST request
-///
-/// The user will create the command, either through a builder pattern or through a
-/// set of structs that impl a trait and then pass it to a command executor
-///
-/// Common things all commands will need:
-/// * Base url
-/// * path to REST endpoint
-/// * HTTP Method
-/// * Body/payload
-/// * headers
-/// * if trait, a common 'execute' method
-use reqwest::Method;
-use url::Url;
+//! The raven commands are the only way to directly interact with the server.
+//!
+//! Every operation is a type implementing [`RavenCommand`]. The command builds its HTTP request
+//! against whichever [`ServerNode`] the [`RequestExecutor`](crate::RequestExecutor) picks for
+//! it, and turns the server's response into its own [`RavenCommand::Result`]. Commands that
+//! aren't part of this library can be written the same way and run through the same executor,
+//! and one-off requests to endpoints without a command of their own can use [`RawCommand`]:
+//!
+//! ```no_run
+//! use ravendb_client::{raven_command::RavenCommand, server_node::ServerNode};
+//!
+//! struct GetBuildNumberCommand;
+//!
+//! impl RavenCommand for GetBuildNumberCommand {
+//!     type Result = serde_json::Value;
+//!
+//!     fn create_request(&self, node: &ServerNode) -> anyhow::Result<reqwest::Request> {
+//!         Ok(reqwest::Request::new(
+//!             reqwest::Method::GET,
+//!             node.url.join("build/version")?,
+//!         ))
+//!     }
+//!
+//!     fn is_read_request(&self) -> bool {
+//!         true
+//!     }
+//!
+//!     async fn parse_response(
+//!         &self,
+//!         response: reqwest::Response,
+//!     ) -> anyhow::Result<Self::Result> {
+//!         Ok(response.error_for_status()?.json().await?)
+//!     }
+//! }
+//! ```
 
-#[derive(Debug)]
-pub struct RavenCommand {
-    pub base_server_url: Url,
-    pub command: RavenCommandVariant,
-}
+mod batch;
+mod get_all_documents;
+mod get_client_configuration;
+mod get_cluster_topology;
+mod get_database_topology;
+mod next_hilo;
+mod raw_command;
 
-impl RavenCommand {
-    /// Returns a [`reqwest::Request`] for the specific [`RavenCommandVariant`].
-    pub fn get_http_request(&self) -> anyhow::Result<reqwest::Request> {
-        //TODO: Replace this client with one passed in somehow from the client cache
-        let client = reqwest::Client::new();
+pub use batch::BatchCommand;
+pub use get_all_documents::GetAllDocumentsCommand;
+pub use get_client_configuration::GetClientConfigurationCommand;
+pub use get_cluster_topology::GetClusterTopologyCommand;
+pub use get_database_topology::GetDatabaseTopologyCommand;
+pub use next_hilo::{HiLoResult, NextHiLoCommand};
+pub use raw_command::RawCommand;
 
-        let request_config = RequestConfig {
-            client,
-            base_url: self.base_server_url.to_owned(),
-        };
+use std::{future::Future, time::Duration};
 
-        // Handle specific command options
-        let request = match &self.command {
-            RavenCommandVariant::GetClusterTopology => {
-                create_get_cluster_topology_request(request_config)?
-            }
-            RavenCommandVariant::GetAllDocumentsFromDatabase {
-                database,
-                page_size,
-                start,
-            } => create_get_all_documents_from_database_request(
-                request_config,
-                database.clone(),
-                *page_size,
-                *start,
-            )?,
-        };
+use anyhow::Context;
+use serde::de::DeserializeOwned;
 
-        Ok(request)
-    }
-}
+use crate::{ravendb_error::RavenDbError, server_node::ServerNode};
 
-fn create_get_cluster_topology_request(config: RequestConfig) -> anyhow::Result<reqwest::Request> {
-    let request = config
-        .client
-        .request(Method::GET, config.base_url.join("cluseter/topology")?)
-        .build()?;
-    Ok(request)
-}
+/// An operation that can be sent to the server by a [`RequestExecutor`](crate::RequestExecutor).
+pub trait RavenCommand: Send + Sync + 'static {
+    /// What the command returns once the server's response has been parsed.
+    type Result: Send;
 
-fn create_get_all_documents_from_database_request(
-    config: RequestConfig,
-    database: String,
-    page_size: Option<i64>,
-    start: Option<i64>,
-) -> anyhow::Result<reqwest::Request> {
-    //Create a vec to hold optional parts of the query string
-    let mut query_string_parts = Vec::new();
+    /// Builds the HTTP request that runs this command on `node`.
+    ///
+    /// This may be called more than once if the request has to be retried on another node.
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<reqwest::Request>;
 
-    // Check if `page_size` and `start` are Some and add to the vec if so
-    if let Some(page_size) = page_size {
-        query_string_parts.push(format!("pageSize={}", page_size))
-    }
-    if let Some(start) = start {
-        query_string_parts.push(format!("start={}", start))
-    }
+    /// Whether the command only reads data. Read-only commands may be balanced across the nodes
+    /// of the topology; anything else always goes to the preferred node.
+    fn is_read_request(&self) -> bool;
 
-    // Make the full query string by joining the parts with &
-    let query_string = query_string_parts.join("&");
+    /// Whether unsuccessful responses are passed to [`parse_response`](Self::parse_response)
+    /// instead of being turned into a [`RavenDbError`](crate::ravendb_error::RavenDbError).
+    /// Responses from nodes that are down always fail over to the next node regardless.
+    fn handles_error_responses(&self) -> bool {
+        false
+    }
 
-    let mut url = config
-        .base_url
-        .join("databases/")?
-        .join(format!("{}/", database).as_str())?
-        .join("docs")?;
-    url.set_query(Some(query_string.as_str()));
+    /// How long each attempt to send the command may take before it is aborted and the next
+    /// node is tried. Defaults to the store's
+    /// [`request_timeout`](crate::DocumentConventions::request_timeout).
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
 
-    let request = config.client.request(Method::GET, url).build()?;
+    /// Turns the server's response into the command's result.
+    fn parse_response(
+        &self,
+        response: reqwest::Response,
+    ) -> impl Future<Output = anyhow::Result<Self::Result>> + Send;
+}
 
-    Ok(request)
+/// Returns the url of `path` on the node's server.
+fn server_url(node: &ServerNode, path: &str) -> anyhow::Result<url::Url> {
+    Ok(node.url.join(path)?)
 }
-/// Represents all operations that can be sent to the server.
-/// Contained inside a [`RavenCommand`]. Holds all data relevant
-/// to the specific command to be sent.
-#[derive(Debug)]
-pub enum RavenCommandVariant {
-    GetClusterTopology,
-    GetAllDocumentsFromDatabase {
-        database: String,
-        page_size: Option<i64>,
-        start: Option<i64>,
-    },
+
+/// Returns the url of `path` inside the node's database.
+pub(crate) fn database_url(node: &ServerNode, path: &str) -> anyhow::Result<url::Url> {
+    Ok(node
+        .url
+        .join("databases/")?
+        .join(format!("{}/", node.database).as_str())?
+        .join(path)?)
 }
 
-#[derive(Debug)]
-pub struct RequestConfig {
-    client: reqwest::Client,
-    base_url: Url,
+/// Deserializes the body of a successful response.
+async fn parse_json<T: DeserializeOwned>(
+    response: reqwest::Response,
+    description: &str,
+) -> anyhow::Result<T> {
+    if !response.status().is_success() {
+        return Err(
+            anyhow::Error::new(RavenDbError::from_response(response).await)
+                .context(format!("Server refused to send the {}", description)),
+        );
+    }
+
+    response
+        .json::<T>()
+        .await
+        .with_context(|| format!("Unable to deserialize {}", description))
 }
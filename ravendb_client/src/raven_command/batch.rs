@@ -0,0 +1,39 @@
+use reqwest::{
+    header::{HeaderValue, CONTENT_TYPE},
+    Method, Request,
+};
+
+use crate::server_node::ServerNode;
+
+use super::{database_url, parse_json, RavenCommand};
+
+/// Runs commands such as `PUT` and `DELETE` on several documents in a single transaction.
+#[derive(Debug, Default)]
+pub struct BatchCommand {
+    /// The commands in the server's format, such as
+    /// `{ "Id": "orders/1-A", "Type": "PUT", "Document": { .. } }`.
+    pub commands: Vec<serde_json::Value>,
+}
+
+impl RavenCommand for BatchCommand {
+    type Result = serde_json::Value;
+
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request> {
+        let body = serde_json::json!({ "Commands": self.commands });
+
+        let mut request = Request::new(Method::POST, database_url(node, "bulk_docs")?);
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        *request.body_mut() = Some(serde_json::to_vec(&body)?.into());
+        Ok(request)
+    }
+
+    fn is_read_request(&self) -> bool {
+        false
+    }
+
+    async fn parse_response(&self, response: reqwest::Response) -> anyhow::Result<Self::Result> {
+        parse_json(response, "batch results").await
+    }
+}
@@ -0,0 +1,93 @@
+use reqwest::{Method, Request};
+
+use crate::server_node::ServerNode;
+
+use super::{database_url, parse_json, RavenCommand};
+
+/// Downloads a page of the documents in the node's database.
+#[derive(Debug, Default)]
+pub struct GetAllDocumentsCommand {
+    pub page_size: Option<i64>,
+    pub start: Option<i64>,
+}
+
+impl RavenCommand for GetAllDocumentsCommand {
+    type Result = serde_json::Value;
+
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request> {
+        let mut url = database_url(node, "docs")?;
+
+        // Only add the paging parameters that were actually set
+        {
+            let mut query = url.query_pairs_mut();
+            if let Some(page_size) = self.page_size {
+                query.append_pair("pageSize", page_size.to_string().as_str());
+            }
+            if let Some(start) = self.start {
+                query.append_pair("start", start.to_string().as_str());
+            }
+        }
+        if url.query() == Some("") {
+            url.set_query(None);
+        }
+
+        Ok(Request::new(Method::GET, url))
+    }
+
+    fn is_read_request(&self) -> bool {
+        true
+    }
+
+    async fn parse_response(&self, response: reqwest::Response) -> anyhow::Result<Self::Result> {
+        parse_json(response, "documents").await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use crate::{raven_command::RavenCommand, server_node::ServerNode};
+
+    use super::GetAllDocumentsCommand;
+
+    fn node() -> ServerNode {
+        ServerNode::new(
+            Url::parse("http://a.example.com").unwrap(),
+            "db".to_string(),
+        )
+    }
+
+    #[test]
+    fn create_request_adds_paging_parameters() {
+        // Arrange
+        let command = GetAllDocumentsCommand {
+            page_size: Some(10),
+            start: Some(20),
+        };
+
+        // Act
+        let request = command.create_request(&node()).unwrap();
+
+        // Assert
+        assert_eq!(
+            request.url().as_str(),
+            "http://a.example.com/databases/db/docs?pageSize=10&start=20"
+        );
+    }
+
+    #[test]
+    fn create_request_without_paging_has_no_query() {
+        // Arrange
+        let command = GetAllDocumentsCommand::default();
+
+        // Act
+        let request = command.create_request(&node()).unwrap();
+
+        // Assert
+        assert_eq!(
+            request.url().as_str(),
+            "http://a.example.com/databases/db/docs"
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use reqwest::{Method, Request};
+
+use crate::{client_configuration::GetClientConfigurationResult, server_node::ServerNode};
+
+use super::{database_url, parse_json, RavenCommand};
+
+/// Downloads the client configuration the server has set for the node's database.
+#[derive(Debug, Default)]
+pub struct GetClientConfigurationCommand;
+
+impl RavenCommand for GetClientConfigurationCommand {
+    type Result = GetClientConfigurationResult;
+
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request> {
+        Ok(Request::new(
+            Method::GET,
+            database_url(node, "configuration/client")?,
+        ))
+    }
+
+    fn is_read_request(&self) -> bool {
+        true
+    }
+
+    async fn parse_response(&self, response: reqwest::Response) -> anyhow::Result<Self::Result> {
+        parse_json(response, "client configuration").await
+    }
+}
@@ -0,0 +1,28 @@
+use reqwest::{Method, Request};
+
+use crate::{cluster_topology::ClusterTopologyInfo, server_node::ServerNode};
+
+use super::{parse_json, server_url, RavenCommand};
+
+/// Downloads the topology of the cluster the node belongs to.
+#[derive(Debug, Default)]
+pub struct GetClusterTopologyCommand;
+
+impl RavenCommand for GetClusterTopologyCommand {
+    type Result = ClusterTopologyInfo;
+
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request> {
+        Ok(Request::new(
+            Method::GET,
+            server_url(node, "cluster/topology")?,
+        ))
+    }
+
+    fn is_read_request(&self) -> bool {
+        true
+    }
+
+    async fn parse_response(&self, response: reqwest::Response) -> anyhow::Result<Self::Result> {
+        parse_json(response, "cluster topology information").await
+    }
+}
@@ -0,0 +1,78 @@
+use reqwest::{Method, Request};
+use uuid::Uuid;
+
+use crate::{
+    database_topology::{DatabaseTopology, GetDatabaseTopologyResult},
+    server_node::ServerNode,
+};
+
+use super::{parse_json, server_url, RavenCommand};
+
+/// Downloads the topology of the node's database.
+#[derive(Debug, Default)]
+pub struct GetDatabaseTopologyCommand {
+    /// Identifies the client to the server, which lists it among the database's clients. Only
+    /// sent when [`send_application_identified`](crate::DocumentConventions::send_application_identified)
+    /// is on.
+    pub application_identifier: Option<Uuid>,
+}
+
+impl RavenCommand for GetDatabaseTopologyCommand {
+    type Result = DatabaseTopology;
+
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request> {
+        let mut url = server_url(node, "topology")?;
+        url.query_pairs_mut()
+            .append_pair("name", node.database.as_str());
+        if let Some(application_identifier) = self.application_identifier {
+            url.query_pairs_mut()
+                .append_pair("applicationIdentifier", &application_identifier.to_string());
+        }
+
+        Ok(Request::new(Method::GET, url))
+    }
+
+    fn is_read_request(&self) -> bool {
+        true
+    }
+
+    async fn parse_response(&self, response: reqwest::Response) -> anyhow::Result<Self::Result> {
+        let result = parse_json::<GetDatabaseTopologyResult>(response, "database topology").await?;
+        Ok(result.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+    use uuid::Uuid;
+
+    use crate::{raven_command::RavenCommand, server_node::ServerNode};
+
+    use super::GetDatabaseTopologyCommand;
+
+    #[test]
+    fn create_request_identifies_the_application() {
+        // Arrange
+        let node = ServerNode::new(
+            Url::parse("http://a.example.com").unwrap(),
+            "db".to_string(),
+        );
+        let application_identifier = Uuid::new_v4();
+        let command = GetDatabaseTopologyCommand {
+            application_identifier: Some(application_identifier),
+        };
+
+        // Act
+        let request = command.create_request(&node).unwrap();
+
+        // Assert
+        assert_eq!(
+            request.url().as_str(),
+            format!(
+                "http://a.example.com/topology?name=db&applicationIdentifier={}",
+                application_identifier
+            )
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use reqwest::{Method, Request};
+use serde::Deserialize;
+
+use crate::server_node::ServerNode;
+
+use super::{database_url, parse_json, RavenCommand};
+
+/// Reserves the next range of ids of a collection, for generating document ids on the client
+/// with the HiLo algorithm.
+#[derive(Debug, Default)]
+pub struct NextHiLoCommand {
+    /// The id prefix of the collection, such as `orders`.
+    pub tag: String,
+    /// Size of the previous range, which the server grows when ranges run out quickly.
+    pub last_batch_size: i64,
+    /// When the previous range was reserved, as returned by the server.
+    pub last_range_at: Option<String>,
+    pub identity_parts_separator: char,
+    /// Highest id of the previous range.
+    pub last_max: i64,
+}
+
+impl RavenCommand for NextHiLoCommand {
+    type Result = HiLoResult;
+
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request> {
+        let mut url = database_url(node, "hilo/next")?;
+        {
+            let mut query = url.query_pairs_mut();
+            query
+                .append_pair("tag", self.tag.as_str())
+                .append_pair("lastBatchSize", self.last_batch_size.to_string().as_str());
+            if let Some(last_range_at) = &self.last_range_at {
+                query.append_pair("lastRangeAt", last_range_at.as_str());
+            }
+            query
+                .append_pair(
+                    "identityPartsSeparator",
+                    self.identity_parts_separator.to_string().as_str(),
+                )
+                .append_pair("lastMax", self.last_max.to_string().as_str());
+        }
+
+        Ok(Request::new(Method::GET, url))
+    }
+
+    // Ranges must come from the node every other write goes to
+    fn is_read_request(&self) -> bool {
+        false
+    }
+
+    async fn parse_response(&self, response: reqwest::Response) -> anyhow::Result<Self::Result> {
+        parse_json(response, "next HiLo range").await
+    }
+}
+
+/// A range of ids reserved for the client. Ids `low` to `high` are its own.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HiLoResult {
+    /// The collection's id prefix, separator included, such as `orders/`.
+    pub prefix: String,
+    pub low: i64,
+    pub high: i64,
+    pub last_size: i64,
+    /// Tag of the node that reserved the range, appended to ids so nodes never hand out the
+    /// same one.
+    pub server_tag: String,
+    pub last_range_at: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use crate::{raven_command::RavenCommand, server_node::ServerNode};
+
+    use super::NextHiLoCommand;
+
+    #[test]
+    fn create_request_sends_the_previous_range() {
+        // Arrange
+        let node = ServerNode::new(
+            Url::parse("http://a.example.com").unwrap(),
+            "db".to_string(),
+        );
+        let command = NextHiLoCommand {
+            tag: "orders".to_string(),
+            last_batch_size: 32,
+            last_range_at: None,
+            identity_parts_separator: '/',
+            last_max: 32,
+        };
+
+        // Act
+        let request = command.create_request(&node).unwrap();
+
+        // Assert
+        assert_eq!(
+            request.url().as_str(),
+            "http://a.example.com/databases/db/hilo/next?tag=orders&lastBatchSize=32&identityPartsSeparator=%2F&lastMax=32"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use reqwest::{header::HeaderMap, Method, Request};
+
+use crate::server_node::ServerNode;
+
+use super::{database_url, server_url, RavenCommand};
+
+/// Sends an arbitrary REST request, for server endpoints this library has no command for yet.
+///
+/// The request still goes through the [`RequestExecutor`](crate::RequestExecutor), so it is
+/// routed, authenticated and failed over like any other command. The response is returned as
+/// is, without checking its status.
+#[derive(Clone, Debug, Default)]
+pub struct RawCommand {
+    pub method: Method,
+    /// Path of the endpoint. Relative paths like `stats` are resolved inside the executor's
+    /// database (`/databases/{database}/stats`), while absolute paths like `/build/version` are
+    /// resolved against the root of the server.
+    pub path: String,
+    /// Query string parameters, appended in order.
+    pub query: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+    pub headers: HeaderMap,
+    /// Whether the request only reads data, which allows it to be balanced across nodes.
+    pub is_read_request: bool,
+    /// Overrides the store's request timeout for this request.
+    pub timeout: Option<Duration>,
+}
+
+impl RawCommand {
+    /// Creates a command without query, body or headers. Only `GET` requests are considered
+    /// read-only.
+    pub fn new(method: Method, path: impl Into<String>) -> Self {
+        Self {
+            is_read_request: method == Method::GET,
+            method,
+            path: path.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl RavenCommand for RawCommand {
+    type Result = reqwest::Response;
+
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request> {
+        let mut url = match self.path.strip_prefix('/') {
+            Some(path) => server_url(node, path)?,
+            None => database_url(node, self.path.as_str())?,
+        };
+        if !self.query.is_empty() {
+            url.query_pairs_mut().extend_pairs(&self.query);
+        }
+
+        let mut request = Request::new(self.method.clone(), url);
+        *request.headers_mut() = self.headers.clone();
+        *request.body_mut() = self.body.clone().map(Into::into);
+        Ok(request)
+    }
+
+    fn is_read_request(&self) -> bool {
+        self.is_read_request
+    }
+
+    fn handles_error_responses(&self) -> bool {
+        true
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    async fn parse_response(&self, response: reqwest::Response) -> anyhow::Result<Self::Result> {
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::{header::HeaderValue, Method, Url};
+
+    use crate::{raven_command::RavenCommand, server_node::ServerNode};
+
+    use super::RawCommand;
+
+    fn node() -> ServerNode {
+        ServerNode::new(
+            Url::parse("http://a.example.com").unwrap(),
+            "db".to_string(),
+        )
+    }
+
+    #[test]
+    fn create_request_resolves_relative_paths_inside_the_database() {
+        // Arrange
+        let mut command = RawCommand::new(Method::GET, "indexes/stats");
+        command
+            .query
+            .push(("name".to_string(), "Orders/ByDate".to_string()));
+
+        // Act
+        let request = command.create_request(&node()).unwrap();
+
+        // Assert
+        assert_eq!(
+            request.url().as_str(),
+            "http://a.example.com/databases/db/indexes/stats?name=Orders%2FByDate"
+        );
+    }
+
+    #[test]
+    fn create_request_resolves_absolute_paths_against_the_server() {
+        // Arrange
+        let mut command = RawCommand::new(Method::POST, "/admin/databases");
+        command.body = Some(b"{}".to_vec());
+        command
+            .headers
+            .insert("Content-Type", HeaderValue::from_static("application/json"));
+
+        // Act
+        let request = command.create_request(&node()).unwrap();
+
+        // Assert
+        assert!(!command.is_read_request());
+        assert_eq!(
+            request.url().as_str(),
+            "http://a.example.com/admin/databases"
+        );
+        assert_eq!(request.headers()["Content-Type"], "application/json");
+        assert_eq!(request.body().unwrap().as_bytes(), Some(&b"{}"[..]));
+    }
+}
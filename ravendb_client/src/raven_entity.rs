@@ -0,0 +1,89 @@
+use serde::Serialize;
+
+use crate::DocumentConventions;
+
+/// A type stored as documents of a collection, such as `Order` in the `Orders` collection.
+///
+/// ```
+/// use ravendb_client::RavenEntity;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Order {
+///     id: Option<String>,
+///     total: f64,
+/// }
+///
+/// impl RavenEntity for Order {
+///     fn id(&self) -> Option<&str> {
+///         self.id.as_deref()
+///     }
+///
+///     fn set_id(&mut self, id: String) {
+///         self.id = Some(id);
+///     }
+/// }
+/// ```
+pub trait RavenEntity: Serialize {
+    /// Name of the collection the entity's documents belong to. Defaults to the store's
+    /// [`find_collection_name`](DocumentConventions::find_collection_name) of the type's name,
+    /// which pluralizes it: `Order` becomes `Orders`.
+    fn collection_name(conventions: &DocumentConventions) -> String
+    where
+        Self: Sized,
+    {
+        conventions.find_collection_name(short_type_name::<Self>())
+    }
+
+    /// The document id, or `None` until the entity is stored by a session.
+    fn id(&self) -> Option<&str>;
+
+    /// Called by the session with the id it generated for the entity.
+    fn set_id(&mut self, id: String);
+}
+
+/// Returns the name of `T` without its module path and generic parameters, such as `Order` for
+/// `app::models::Order<u32>`.
+pub(crate) fn short_type_name<T: ?Sized>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Serialize;
+
+    use crate::DocumentConventions;
+
+    use super::{short_type_name, RavenEntity};
+
+    #[derive(Serialize)]
+    struct Company {
+        id: Option<String>,
+    }
+
+    impl RavenEntity for Company {
+        fn id(&self) -> Option<&str> {
+            self.id.as_deref()
+        }
+
+        fn set_id(&mut self, id: String) {
+            self.id = Some(id);
+        }
+    }
+
+    #[test]
+    fn collection_name_defaults_to_pluralized_type_name() {
+        assert_eq!(
+            Company::collection_name(&DocumentConventions::default()),
+            "Companies"
+        );
+    }
+
+    #[test]
+    fn short_type_name_strips_path_and_generics() {
+        assert_eq!(short_type_name::<Company>(), "Company");
+        assert_eq!(short_type_name::<Vec<Company>>(), "Vec");
+    }
+}
@@ -1,11 +1,81 @@
+use reqwest::{header::HeaderMap, StatusCode, Url};
+use serde::Deserialize;
+
 use crate::error_chain_fmt;
 
+/// Errors reported by the server, or raised by the client while talking to it.
+///
+/// Errors the server describes in its response body are mapped from the exception type it
+/// names. When the server includes its stack trace, it is available as the error's
+/// [`source`](std::error::Error::source).
 #[derive(thiserror::Error)]
 pub enum RavenDbError {
+    #[error("All nodes of the topology failed to respond: {}", format_node_errors(.errors))]
+    AllTopologyNodesDown { errors: Vec<(Url, RavenDbError)> },
+    #[deprecated(note = "the server's authorization errors are reported as `AuthorizationFailed`")]
     #[error("Invalid authorization, ensure valid certificate supplied")]
     BadAuthorization,
+    #[error("{message}")]
+    AuthorizationFailed {
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
+    #[error("{message}")]
+    ConcurrencyViolation {
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
+    #[error("{message}")]
+    DatabaseDisabled {
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
     #[error("Database `{0}` does not exist")]
     DatabaseDoesNotExist(String),
+    #[error("{message}")]
+    DatabaseLoadTimeout {
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
+    #[error("{message}")]
+    DocumentDoesNotExist {
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
+    #[error("{message}")]
+    IndexDoesNotExist {
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
+    #[error("{message}")]
+    InvalidQuery {
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
+    #[error("Unable to reach the server")]
+    RequestFailed(#[source] reqwest::Error),
+    /// The server returned an error this library has no variant for.
+    #[error("Server responded with {status}: {message}")]
+    ServerError {
+        status: StatusCode,
+        error_type: Option<String>,
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
+    #[error("{message}")]
+    Timeout {
+        message: String,
+        #[source]
+        server_stack_trace: Option<ServerStackTrace>,
+    },
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -14,3 +84,305 @@ impl std::fmt::Debug for RavenDbError {
         error_chain_fmt(self, f)
     }
 }
+
+impl From<reqwest::Error> for RavenDbError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return RavenDbError::Timeout {
+                message: format!("The request timed out: {}", e),
+                server_stack_trace: None,
+            };
+        }
+        RavenDbError::RequestFailed(e)
+    }
+}
+
+impl RavenDbError {
+    /// Reads the error the server described in an unsuccessful response.
+    pub async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let url = response.url().clone();
+        let headers = response.headers().clone();
+        let body = match response.text().await {
+            Ok(body) => body,
+            Err(e) => return e.into(),
+        };
+        RavenDbError::from_parts(status, &url, &headers, &body)
+    }
+
+    /// Whether the error means the node couldn't serve the request, so it should be retried on
+    /// another node of the topology.
+    pub fn is_node_failure(&self) -> bool {
+        match self {
+            RavenDbError::RequestFailed(_)
+            | RavenDbError::Timeout { .. }
+            | RavenDbError::DatabaseLoadTimeout { .. } => true,
+            RavenDbError::ServerError { status, .. } => is_node_failure_status(*status),
+            _ => false,
+        }
+    }
+
+    /// Maps a response of the request sent to `url`. The url supplies the database name when
+    /// the server reports a missing database without the `Database-Missing` header.
+    fn from_parts(status: StatusCode, url: &Url, headers: &HeaderMap, body: &str) -> Self {
+        if let Some(database) = headers
+            .get("Database-Missing")
+            .and_then(|value| value.to_str().ok())
+        {
+            return RavenDbError::DatabaseDoesNotExist(database.to_string());
+        }
+
+        // Not every error has a JSON body, proxies in particular answer with plain text
+        let body =
+            serde_json::from_str::<ServerErrorBody>(body).unwrap_or_else(|_| ServerErrorBody {
+                message: (!body.trim().is_empty()).then(|| body.trim().to_string()),
+                ..Default::default()
+            });
+
+        let message = body.message.unwrap_or_else(|| {
+            status
+                .canonical_reason()
+                .unwrap_or("Unknown error")
+                .to_string()
+        });
+        let server_stack_trace = body.error.map(ServerStackTrace);
+
+        // The type is the full name of the .NET exception, like
+        // `Raven.Client.Exceptions.ConcurrencyException`
+        let exception = body
+            .error_type
+            .as_deref()
+            .and_then(|error_type| error_type.rsplit('.').next());
+
+        let database = database_of(url);
+
+        match (exception, status) {
+            (Some("ConcurrencyException"), _) | (None, StatusCode::CONFLICT) => {
+                RavenDbError::ConcurrencyViolation {
+                    message,
+                    server_stack_trace,
+                }
+            }
+            (Some("DocumentDoesNotExistException"), _) => RavenDbError::DocumentDoesNotExist {
+                message,
+                server_stack_trace,
+            },
+            (Some("IndexDoesNotExistException"), _) => RavenDbError::IndexDoesNotExist {
+                message,
+                server_stack_trace,
+            },
+            (Some("DatabaseDisabledException"), _) => RavenDbError::DatabaseDisabled {
+                message,
+                server_stack_trace,
+            },
+            (Some("DatabaseLoadTimeoutException"), _) => RavenDbError::DatabaseLoadTimeout {
+                message,
+                server_stack_trace,
+            },
+            (Some("DatabaseDoesNotExistException"), _) if database.is_some() => {
+                RavenDbError::DatabaseDoesNotExist(database.unwrap_or_default())
+            }
+            (Some("InvalidQueryException"), _) => RavenDbError::InvalidQuery {
+                message,
+                server_stack_trace,
+            },
+            (Some("AuthorizationException" | "SecurityException"), _)
+            | (None, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                RavenDbError::AuthorizationFailed {
+                    message,
+                    server_stack_trace,
+                }
+            }
+            (Some("TimeoutException" | "RavenTimeoutException"), _) => RavenDbError::Timeout {
+                message,
+                server_stack_trace,
+            },
+            _ => RavenDbError::ServerError {
+                status,
+                error_type: body.error_type,
+                message,
+                server_stack_trace,
+            },
+        }
+    }
+}
+
+/// The stack trace the server sent along with an error.
+#[derive(Debug)]
+pub struct ServerStackTrace(pub String);
+
+impl std::fmt::Display for ServerStackTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ServerStackTrace {}
+
+/// The JSON body the server sends with most unsuccessful responses.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct ServerErrorBody {
+    #[serde(rename = "Type")]
+    error_type: Option<String>,
+    message: Option<String>,
+    error: Option<String>,
+}
+
+/// Whether a response with this status means the node is down or overloaded.
+pub(crate) fn is_node_failure_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Returns the database a request was sent to, from the `databases/{name}` segments of its path
+/// or, for topology requests, from its `name` query parameter.
+fn database_of(url: &Url) -> Option<String> {
+    let mut segments = url.path_segments()?;
+    if segments.any(|segment| segment == "databases") {
+        return segments
+            .next()
+            .filter(|database| !database.is_empty())
+            .map(str::to_string);
+    }
+    url.query_pairs()
+        .find(|(key, _)| key == "name")
+        .map(|(_, database)| database.into_owned())
+}
+
+fn format_node_errors(errors: &[(Url, RavenDbError)]) -> String {
+    errors
+        .iter()
+        .map(|(url, e)| format!("`{}`: {}", url, e))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use reqwest::{
+        header::{HeaderMap, HeaderValue},
+        StatusCode, Url,
+    };
+
+    use super::RavenDbError;
+
+    fn url() -> Url {
+        Url::parse("http://a.example.com/databases/Orders/docs").unwrap()
+    }
+
+    #[test]
+    fn from_parts_maps_exception_type_and_keeps_stack_trace() {
+        // Arrange
+        let body = serde_json::json!({
+            "Url": "/databases/db/docs",
+            "Type": "Raven.Client.Exceptions.ConcurrencyException",
+            "Message": "Document orders/1-A has change vector A:2, expected A:1",
+            "Error": "Raven.Client.Exceptions.ConcurrencyException: ...\n   at Raven.Server..."
+        })
+        .to_string();
+
+        // Act
+        let result =
+            RavenDbError::from_parts(StatusCode::CONFLICT, &url(), &HeaderMap::new(), &body);
+
+        // Assert
+        assert!(matches!(
+            &result,
+            RavenDbError::ConcurrencyViolation { message, .. }
+                if message == "Document orders/1-A has change vector A:2, expected A:1"
+        ));
+        assert!(result
+            .source()
+            .unwrap()
+            .to_string()
+            .starts_with("Raven.Client.Exceptions.ConcurrencyException"));
+    }
+
+    #[test]
+    fn from_parts_reads_missing_database_header() {
+        // Arrange
+        let mut headers = HeaderMap::new();
+        headers.insert("Database-Missing", HeaderValue::from_static("Orders"));
+
+        // Act
+        let result =
+            RavenDbError::from_parts(StatusCode::SERVICE_UNAVAILABLE, &url(), &headers, "");
+
+        // Assert
+        assert!(
+            matches!(result, RavenDbError::DatabaseDoesNotExist(database) if database == "Orders")
+        );
+        assert!(!RavenDbError::DatabaseDoesNotExist("Orders".to_string()).is_node_failure());
+    }
+
+    #[test]
+    fn from_parts_names_the_missing_database_from_the_url() {
+        // Arrange
+        let body = serde_json::json!({
+            "Type": "Raven.Client.Exceptions.Database.DatabaseDoesNotExistException",
+            "Message": "Database 'Orders' does not exist."
+        })
+        .to_string();
+        let topology_url = Url::parse("http://a.example.com/topology?name=Orders").unwrap();
+
+        // Act
+        let result = RavenDbError::from_parts(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &url(),
+            &HeaderMap::new(),
+            &body,
+        );
+        let topology_result = RavenDbError::from_parts(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &topology_url,
+            &HeaderMap::new(),
+            &body,
+        );
+
+        // Assert
+        assert_eq!(result.to_string(), "Database `Orders` does not exist");
+        assert_eq!(
+            topology_result.to_string(),
+            "Database `Orders` does not exist"
+        );
+    }
+
+    #[test]
+    fn from_parts_falls_back_to_status_without_json_body() {
+        // Act
+        let unauthorized = RavenDbError::from_parts(
+            StatusCode::FORBIDDEN,
+            &url(),
+            &HeaderMap::new(),
+            "Forbidden",
+        );
+        let unavailable = RavenDbError::from_parts(
+            StatusCode::SERVICE_UNAVAILABLE,
+            &url(),
+            &HeaderMap::new(),
+            "",
+        );
+
+        // Assert
+        assert!(matches!(
+            unauthorized,
+            RavenDbError::AuthorizationFailed { message, server_stack_trace: None } if message == "Forbidden"
+        ));
+        assert!(matches!(
+            &unavailable,
+            RavenDbError::ServerError {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                ..
+            }
+        ));
+        assert!(unavailable.is_node_failure());
+    }
+}
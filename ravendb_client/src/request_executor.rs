@@ -1,25 +1,101 @@
+mod changes_listener;
+mod compression;
+mod http_cache;
+mod http_client;
 mod request_executor_actor;
 mod request_executor_error;
 mod request_executor_handle;
+mod topology_cache;
 
+pub use http_cache::{AggressiveCacheGuard, HttpCacheStats};
+pub(crate) use http_client::{HttpClientSettings, TrustStore};
 pub use request_executor_actor::RequestExecutorActor;
 pub use request_executor_error::RequestExecutorError;
 pub use request_executor_handle::RequestExecutor;
-use reqwest::{Response, Url};
+use std::{sync::Arc, time::Duration};
+
+use reqwest::{Request, Response, Url};
 use tokio::sync::oneshot;
 
-use crate::{database_topology::DatabaseTopology, raven_command::RavenCommand};
+use crate::{
+    client_configuration::GetClientConfigurationResult, cluster_topology::ClusterTopology,
+    database_topology::DatabaseTopology, raven_command::RavenCommand, server_node::ServerNode,
+    DocumentConventions, SessionInfo,
+};
 
 pub(crate) enum RequestExecutorMessage {
     ExecuteRavenCommand {
         respond_to: oneshot::Sender<Result<Response, RequestExecutorError>>,
-        command: RavenCommand,
+        command: Arc<dyn RequestFactory>,
+        session_info: Option<SessionInfo>,
     },
     InitialUpdateTopology {
         initial_urls: Vec<Url>,
+        respond_to: oneshot::Sender<Result<(), RequestExecutorError>>,
+    },
+    /// Returns the conventions with the server's client configuration applied.
+    GetConventions {
+        respond_to: oneshot::Sender<DocumentConventions>,
+    },
+    /// Returns a snapshot of the HTTP cache's size and hit rate.
+    GetHttpCacheStats {
+        respond_to: oneshot::Sender<HttpCacheStats>,
     },
+    /// Rebuilds the HTTP client with a new client certificate, or none. Requests already sent
+    /// finish on the previous client.
+    ReplaceClientCertificate {
+        client_certificate_pem: Option<Vec<u8>>,
+        respond_to: oneshot::Sender<Result<(), RequestExecutorError>>,
+    },
+    /// Starts watching the database's changes for aggressive caching, unless that is already
+    /// running or aggressive caching has been turned off again.
+    WatchChanges,
+    /// Records that a node failed to answer a request, so it is avoided until it recovers.
+    NodeFailed { node: ServerNode },
+    /// Records how long a node took to answer a request, for the `FastestNode` read balancing.
+    RecordResponseTime {
+        node: ServerNode,
+        response_time_ms: u32,
+    },
+    /// Starts a background refresh of the cluster topology, unless one is already running.
+    UpdateClusterTopology,
+    /// Sent by the background cluster topology refresh once the server answered.
+    ClusterTopologyUpdated { topology: ClusterTopology },
+    /// Starts a background refresh of the database topology, unless one is already running.
     UpdateTopology,
-    TopologyUpdated {
-        topology: DatabaseTopology,
+    /// Sent by the background topology refresh once the server answered.
+    TopologyUpdated { topology: DatabaseTopology },
+    /// Starts a background refresh of the client configuration, unless one is already running.
+    UpdateClientConfiguration,
+    /// Sent by the background client configuration refresh once the server answered.
+    ClientConfigurationUpdated {
+        result: GetClientConfigurationResult,
     },
 }
+
+/// The part of a [`RavenCommand`] the actor needs to send it. Unlike [`RavenCommand`] it can be
+/// used as a trait object, so commands of any type fit through the actor's channel.
+pub(crate) trait RequestFactory: Send + Sync {
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request>;
+    fn is_read_request(&self) -> bool;
+    fn handles_error_responses(&self) -> bool;
+    fn timeout(&self) -> Option<Duration>;
+}
+
+impl<C: RavenCommand> RequestFactory for C {
+    fn create_request(&self, node: &ServerNode) -> anyhow::Result<Request> {
+        RavenCommand::create_request(self, node)
+    }
+
+    fn is_read_request(&self) -> bool {
+        RavenCommand::is_read_request(self)
+    }
+
+    fn handles_error_responses(&self) -> bool {
+        RavenCommand::handles_error_responses(self)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        RavenCommand::timeout(self)
+    }
+}
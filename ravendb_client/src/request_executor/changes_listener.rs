@@ -0,0 +1,229 @@
+//! Watches the database's Changes API, so the HTTP cache learns when the documents and indexes
+//! its aggressively cached responses were read from have changed.
+//!
+//! The Changes API is a websocket. It is opened by upgrading a request sent with the executor's
+//! own settings, so certificates, dns overrides and proxies apply to it like to any other request.
+
+use std::sync::Mutex;
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::{SinkExt, StreamExt};
+use reqwest::{
+    header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE},
+    StatusCode,
+};
+use serde::Deserialize;
+use tokio_tungstenite::{
+    tungstenite::{protocol::Role, Message},
+    WebSocketStream,
+};
+
+use crate::{raven_command::database_url, ravendb_error::RavenDbError, server_node::ServerNode};
+
+use super::{
+    http_cache::{lock_http_cache, HttpCache},
+    http_client::HttpClient,
+};
+
+/// Appended to the client's key by the server to prove it speaks the websocket protocol.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Invalidates the HTTP cache every time a document or index of the node's database changes.
+///
+/// Returns once the connection is closed. The cache is invalidated then too, since changes made
+/// while nobody was watching would otherwise go unnoticed. `client` must only speak HTTP/1.1, as
+/// HTTP/2 connections can't be upgraded.
+pub(crate) async fn watch_changes(
+    client: &HttpClient,
+    node: &ServerNode,
+    http_cache: &Mutex<HttpCache>,
+) -> anyhow::Result<()> {
+    let mut socket = connect(client, node).await?;
+
+    for (command_id, command) in [(1, "watch-docs"), (2, "watch-indexes")] {
+        let message = serde_json::json!({ "CommandId": command_id, "Command": command });
+        socket
+            .send(Message::Text(message.to_string()))
+            .await
+            .with_context(|| format!("Unable to send `{}` to the Changes API", command))?;
+    }
+    lock_http_cache(http_cache).set_watching_changes(true);
+    tracing::debug!("Watching changes of database `{}`", node.database);
+
+    let result = async {
+        while let Some(message) = socket.next().await {
+            match message.context("Lost the connection to the Changes API")? {
+                Message::Text(text) if invalidates_cache(&text) => {
+                    lock_http_cache(http_cache).invalidate();
+                }
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    let mut http_cache = lock_http_cache(http_cache);
+    http_cache.set_watching_changes(false);
+    http_cache.invalidate();
+    result
+}
+
+/// Opens the websocket of the node's Changes API.
+async fn connect(
+    client: &HttpClient,
+    node: &ServerNode,
+) -> anyhow::Result<WebSocketStream<reqwest::Upgraded>> {
+    let key = STANDARD.encode(rand::random::<[u8; 16]>());
+    let response = client
+        .get(database_url(node, "changes")?)
+        .map_err(RavenDbError::from)?
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_VERSION, "13")
+        .header(SEC_WEBSOCKET_KEY, &key)
+        .send()
+        .await
+        .map_err(RavenDbError::from)?;
+
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return Err(
+            anyhow::Error::new(RavenDbError::from_response(response).await)
+                .context("Server refused to open the Changes API"),
+        );
+    }
+    let accept = response
+        .headers()
+        .get(SEC_WEBSOCKET_ACCEPT)
+        .and_then(|value| value.to_str().ok());
+    if accept != Some(accept_key(&key).as_str()) {
+        anyhow::bail!("Server answered the Changes API upgrade with an invalid accept key");
+    }
+
+    let upgraded = response
+        .upgrade()
+        .await
+        .context("Unable to upgrade the connection to the Changes API")?;
+    Ok(WebSocketStream::from_raw_socket(upgraded, Role::Client, None).await)
+}
+
+/// Returns the `Sec-WebSocket-Accept` value the server must answer `key` with.
+fn accept_key(key: &str) -> String {
+    let mut hasher = sha1_smol::Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(WEBSOCKET_GUID.as_bytes());
+    STANDARD.encode(hasher.digest().bytes())
+}
+
+/// A notification sent by the Changes API. Several of them may arrive batched in an array.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ChangesMessage {
+    Batch(Vec<ChangesMessage>),
+    Single {
+        #[serde(rename = "Type")]
+        message_type: Option<String>,
+    },
+}
+
+/// Whether the message reports a changed document or index. Confirmations of the watch commands
+/// and heartbeats don't.
+fn invalidates_cache(text: &str) -> bool {
+    fn is_change(message: &ChangesMessage) -> bool {
+        match message {
+            ChangesMessage::Batch(messages) => messages.iter().any(is_change),
+            ChangesMessage::Single { message_type } => matches!(
+                message_type.as_deref(),
+                Some("DocumentChange" | "IndexChange")
+            ),
+        }
+    }
+
+    match serde_json::from_str::<ChangesMessage>(text) {
+        Ok(message) => is_change(&message),
+        Err(e) => {
+            // Better to revalidate once too often than to serve a stale response
+            tracing::warn!("Unreadable message from the Changes API. Caused by: {}", e);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures_util::{SinkExt, StreamExt};
+    use reqwest::Url;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use crate::{request_executor::http_cache::HttpCache, server_node::ServerNode};
+
+    use super::{accept_key, invalidates_cache, watch_changes};
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn invalidates_cache_only_for_document_and_index_changes() {
+        assert!(invalidates_cache(
+            r#"[{"Type":"DocumentChange","Value":{"Id":"orders/1-A","Type":"Put"}}]"#
+        ));
+        assert!(invalidates_cache(r#"{"Type":"IndexChange","Value":{}}"#));
+        assert!(!invalidates_cache(r#"[{"CommandId":1,"Type":"Confirm"}]"#));
+        assert!(!invalidates_cache(r#"{"Type":"Heartbeat"}"#));
+    }
+
+    #[tokio::test]
+    async fn watch_changes_invalidates_cache_on_document_change() {
+        // Arrange
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let mut commands = Vec::new();
+            for _ in 0..2 {
+                commands.push(socket.next().await.unwrap().unwrap().into_text().unwrap());
+            }
+            socket
+                .send(Message::Text(
+                    r#"[{"Type":"DocumentChange","Value":{"Id":"orders/1-A"}}]"#.to_string(),
+                ))
+                .await
+                .unwrap();
+            socket.close(None).await.unwrap();
+            commands
+        });
+        let client = reqwest::Client::builder()
+            .http1_only()
+            .build()
+            .unwrap()
+            .into();
+        let http_cache = Mutex::new(HttpCache::new(100));
+
+        // Act
+        watch_changes(
+            &client,
+            &ServerNode::new(url, "db".to_string()),
+            &http_cache,
+        )
+        .await
+        .unwrap();
+
+        // Assert
+        let commands = server.await.unwrap();
+        assert!(commands[0].contains("watch-docs"));
+        assert!(commands[1].contains("watch-indexes"));
+        // Once for the document change, once for the closed connection
+        assert_eq!(http_cache.lock().unwrap().generation(), 2);
+    }
+}
@@ -0,0 +1,176 @@
+//! Compresses request bodies and decompresses responses with the algorithm picked by the
+//! [`DocumentConventions`](crate::DocumentConventions).
+//!
+//! gzip and brotli responses are decompressed by the HTTP client itself. It doesn't support zstd,
+//! so zstd responses are decompressed here once their body has been read.
+
+use std::io::Write;
+
+use anyhow::Context;
+use reqwest::{
+    header::{HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH},
+    Request, Response,
+};
+
+use crate::{document_conventions::HttpCompressionAlgorithm, ravendb_error::RavenDbError};
+
+/// Bodies smaller than this are sent as is, as compressing them saves next to nothing.
+const MIN_COMPRESSED_BODY_SIZE: usize = 1024;
+
+/// Asks the server to compress the response with `algorithm`, and compresses the request body
+/// with it.
+///
+/// Bodies that are streamed, small, or already encoded by the command are left alone.
+pub(crate) fn compress_request(
+    request: &mut Request,
+    algorithm: HttpCompressionAlgorithm,
+) -> anyhow::Result<()> {
+    let content_encoding = HeaderValue::from_static(algorithm.content_encoding());
+    request
+        .headers_mut()
+        .entry(ACCEPT_ENCODING)
+        .or_insert(content_encoding.clone());
+
+    if request.headers().contains_key(CONTENT_ENCODING) {
+        return Ok(());
+    }
+    let Some(body) = request.body().and_then(|body| body.as_bytes()) else {
+        return Ok(());
+    };
+    if body.len() < MIN_COMPRESSED_BODY_SIZE {
+        return Ok(());
+    }
+
+    let compressed = compress(body, algorithm)
+        .with_context(|| format!("Unable to compress the request body with {:?}", algorithm))?;
+    *request.body_mut() = Some(compressed.into());
+    request
+        .headers_mut()
+        .insert(CONTENT_ENCODING, content_encoding);
+    Ok(())
+}
+
+/// Decompresses the body of a zstd response. Other responses are returned unchanged.
+pub(crate) async fn decompress_response(response: Response) -> Result<Response, RavenDbError> {
+    let is_zstd = response
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().eq_ignore_ascii_case("zstd"))
+        .unwrap_or(false);
+    if !is_zstd {
+        return Ok(response);
+    }
+
+    let status = response.status();
+    let url = response.url().clone();
+    let mut headers = response.headers().clone();
+    headers.remove(CONTENT_ENCODING);
+    headers.remove(CONTENT_LENGTH);
+    let body = response.bytes().await?;
+    let body = zstd::decode_all(body.as_ref()).context("Unable to decompress the zstd response")?;
+
+    use reqwest::ResponseBuilderExt;
+    let mut decompressed = http::Response::builder()
+        .status(status)
+        .url(url)
+        .body(body)
+        .context("Unable to rebuild the decompressed response")?;
+    *decompressed.headers_mut() = headers;
+    Ok(Response::from(decompressed))
+}
+
+fn compress(body: &[u8], algorithm: HttpCompressionAlgorithm) -> std::io::Result<Vec<u8>> {
+    match algorithm {
+        HttpCompressionAlgorithm::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+        HttpCompressionAlgorithm::Brotli => {
+            let mut compressed = Vec::new();
+            {
+                // Quality 5 and a 4 MB window, a balance of speed and size for JSON
+                let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+                encoder.write_all(body)?;
+            }
+            Ok(compressed)
+        }
+        HttpCompressionAlgorithm::Zstd => zstd::encode_all(body, zstd::DEFAULT_COMPRESSION_LEVEL),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use reqwest::{
+        header::{ACCEPT_ENCODING, CONTENT_ENCODING},
+        Method, Request, Url,
+    };
+
+    use crate::document_conventions::HttpCompressionAlgorithm;
+
+    use super::{compress_request, decompress_response};
+
+    fn request_with_body(body: Vec<u8>) -> Request {
+        let mut request = Request::new(
+            Method::POST,
+            Url::parse("http://localhost:8080/databases/db/bulk_docs").unwrap(),
+        );
+        *request.body_mut() = Some(body.into());
+        request
+    }
+
+    #[test]
+    fn compress_request_compresses_large_bodies() {
+        // Arrange
+        let body = br#"{"Commands":[]}"#.repeat(100);
+        let mut request = request_with_body(body.clone());
+
+        // Act
+        compress_request(&mut request, HttpCompressionAlgorithm::Gzip).unwrap();
+
+        // Assert
+        assert_eq!(request.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(request.headers()[ACCEPT_ENCODING], "gzip");
+        let compressed = request.body().unwrap().as_bytes().unwrap();
+        let mut decompressed = Vec::new();
+        flate2::read::GzDecoder::new(compressed)
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, body);
+    }
+
+    #[test]
+    fn compress_request_leaves_small_bodies_alone() {
+        // Arrange
+        let mut request = request_with_body(b"{}".to_vec());
+
+        // Act
+        compress_request(&mut request, HttpCompressionAlgorithm::Brotli).unwrap();
+
+        // Assert
+        assert!(request.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(request.headers()[ACCEPT_ENCODING], "br");
+        assert_eq!(request.body().unwrap().as_bytes().unwrap(), b"{}");
+    }
+
+    #[tokio::test]
+    async fn decompress_response_decodes_zstd() {
+        // Arrange
+        let body = zstd::encode_all(&br#"{"Results":[]}"#[..], 3).unwrap();
+        let response = http::Response::builder()
+            .header(CONTENT_ENCODING, "zstd")
+            .body(body)
+            .unwrap();
+
+        // Act
+        let result = decompress_response(response.into()).await.unwrap();
+
+        // Assert
+        assert!(result.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(result.text().await.unwrap(), r#"{"Results":[]}"#);
+    }
+}
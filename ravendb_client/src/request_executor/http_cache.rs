@@ -0,0 +1,467 @@
+//! Caches the responses to GET requests so they can be revalidated with `If-None-Match`
+//! instead of downloaded again.
+//!
+//! Entries are keyed by the full url of the request and evicted least recently used first once
+//! the bodies stored exceed the configured size.
+//!
+//! While aggressive caching is on, recent entries are returned without asking the server at all.
+//! Every change notification from the server bumps the cache's generation, which stops entries
+//! stored before it from being used that way until they are revalidated.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use reqwest::{header::HeaderMap, header::HeaderValue, StatusCode, Url};
+use tokio::sync::watch;
+
+/// A response stored in the [`HttpCache`].
+#[derive(Clone, Debug)]
+pub(crate) struct CachedResponse {
+    pub(crate) etag: HeaderValue,
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Bytes,
+}
+
+impl CachedResponse {
+    /// Rebuilds a response from the cached parts, as if `url` had just returned it.
+    pub(crate) fn to_response(&self, url: Url) -> reqwest::Response {
+        use reqwest::ResponseBuilderExt;
+
+        let mut response = http::Response::builder()
+            .status(self.status)
+            .url(url)
+            .body(self.body.clone())
+            .expect("Cached status and headers were valid when they were received");
+        *response.headers_mut() = self.headers.clone();
+        reqwest::Response::from(response)
+    }
+
+    fn size(&self) -> usize {
+        self.body.len()
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    response: CachedResponse,
+    /// The value of `HttpCache::clock` when the entry was last used.
+    last_used: u64,
+    /// When the server last confirmed the response was current.
+    stored_at: Instant,
+    /// The value of `HttpCache::generation` when the server last confirmed the response.
+    generation: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct HttpCache {
+    entries: HashMap<String, CacheEntry>,
+    /// Urls by the time they were last used, oldest first.
+    recently_used: BTreeMap<u64, String>,
+    clock: u64,
+    max_size_bytes: usize,
+    size_bytes: usize,
+    hits: u64,
+    misses: u64,
+    /// Bumped whenever the server reports a change, or the changes can no longer be watched.
+    generation: u64,
+    /// How long entries are returned without revalidation, by the id of the guard that asked for
+    /// it. The most recently started one that is still active applies.
+    aggressive_cache_durations: BTreeMap<u64, Duration>,
+    next_aggressive_cache_id: u64,
+    /// Tells the task watching the server's changes when aggressive caching is turned off.
+    aggressive_caching: watch::Sender<bool>,
+    /// Whether the server's changes are being watched. Entries are only served aggressively
+    /// then, as nothing would tell the cache they went stale otherwise.
+    watching_changes: bool,
+}
+
+impl HttpCache {
+    /// Creates a cache holding at most `max_size_bytes` of response bodies. A size of 0
+    /// disables caching.
+    pub(crate) fn new(max_size_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recently_used: BTreeMap::new(),
+            clock: 0,
+            max_size_bytes,
+            size_bytes: 0,
+            hits: 0,
+            misses: 0,
+            generation: 0,
+            aggressive_cache_durations: BTreeMap::new(),
+            next_aggressive_cache_id: 0,
+            aggressive_caching: watch::channel(false).0,
+            watching_changes: false,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.max_size_bytes > 0
+    }
+
+    /// Returns the etag to revalidate the cached response for `url` with, if there is one.
+    pub(crate) fn etag_for(&self, url: &str) -> Option<HeaderValue> {
+        self.entries
+            .get(url)
+            .map(|entry| entry.response.etag.clone())
+    }
+
+    /// Returns the cached response for `url` after the server confirmed it is still current.
+    ///
+    /// `generation` is the cache's generation from before the request was sent, so a change
+    /// reported while it was in flight still invalidates the entry.
+    pub(crate) fn hit(&mut self, url: &str, generation: u64) -> Option<CachedResponse> {
+        let entry = self.entries.get_mut(url)?;
+        entry.stored_at = Instant::now();
+        entry.generation = generation;
+        self.use_entry(url)
+    }
+
+    /// Returns the cached response for `url` without revalidating it, if aggressive caching is
+    /// on and no change was reported since the server last confirmed it.
+    pub(crate) fn aggressive_hit(&mut self, url: &str) -> Option<CachedResponse> {
+        let duration = self
+            .aggressive_cache_duration()
+            .filter(|_| self.watching_changes)?;
+        let entry = self.entries.get(url)?;
+        if entry.generation != self.generation || entry.stored_at.elapsed() >= duration {
+            return None;
+        }
+        self.use_entry(url)
+    }
+
+    fn use_entry(&mut self, url: &str) -> Option<CachedResponse> {
+        self.clock += 1;
+        let entry = self.entries.get_mut(url)?;
+        self.recently_used.remove(&entry.last_used);
+        entry.last_used = self.clock;
+        self.recently_used.insert(self.clock, url.to_string());
+        self.hits += 1;
+        Some(entry.response.clone())
+    }
+
+    /// Records a request the cache could not answer.
+    pub(crate) fn miss(&mut self) {
+        self.misses += 1;
+    }
+
+    /// Stores the response for `url`, evicting the least recently used entries to make room.
+    ///
+    /// `generation` is the cache's generation from before the request was sent.
+    pub(crate) fn insert(&mut self, url: String, response: CachedResponse, generation: u64) {
+        self.remove(&url);
+        if response.size() > self.max_size_bytes {
+            return;
+        }
+
+        while self.size_bytes + response.size() > self.max_size_bytes {
+            let Some((_, oldest)) = self.recently_used.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.size_bytes -= entry.response.size();
+            }
+        }
+
+        self.clock += 1;
+        self.size_bytes += response.size();
+        self.recently_used.insert(self.clock, url.clone());
+        self.entries.insert(
+            url,
+            CacheEntry {
+                response,
+                last_used: self.clock,
+                stored_at: Instant::now(),
+                generation,
+            },
+        );
+    }
+
+    pub(crate) fn remove(&mut self, url: &str) {
+        if let Some(entry) = self.entries.remove(url) {
+            self.recently_used.remove(&entry.last_used);
+            self.size_bytes -= entry.response.size();
+        }
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Stops every entry stored so far from being served aggressively until it is revalidated.
+    pub(crate) fn invalidate(&mut self) {
+        self.generation += 1;
+    }
+
+    pub(crate) fn aggressive_cache_duration(&self) -> Option<Duration> {
+        self.aggressive_cache_durations
+            .last_key_value()
+            .map(|(_, duration)| *duration)
+    }
+
+    /// Serves entries without revalidation for `duration` until [`Self::stop_aggressive_caching`]
+    /// is called with the returned id.
+    pub(crate) fn start_aggressive_caching(&mut self, duration: Duration) -> u64 {
+        let id = self.next_aggressive_cache_id;
+        self.next_aggressive_cache_id += 1;
+        self.aggressive_cache_durations.insert(id, duration);
+        self.aggressive_caching.send_replace(true);
+        id
+    }
+
+    /// Withdraws the duration started under `id`. Aggressive caching stays on while any other
+    /// duration is still active.
+    pub(crate) fn stop_aggressive_caching(&mut self, id: u64) {
+        self.aggressive_cache_durations.remove(&id);
+        self.aggressive_caching
+            .send_replace(!self.aggressive_cache_durations.is_empty());
+    }
+
+    /// Returns a receiver that sees whether aggressive caching is on, see
+    /// [`aggressive_caching_turned_off`].
+    pub(crate) fn subscribe_to_aggressive_caching(&self) -> watch::Receiver<bool> {
+        self.aggressive_caching.subscribe()
+    }
+
+    pub(crate) fn set_watching_changes(&mut self, watching_changes: bool) {
+        self.watching_changes = watching_changes;
+    }
+
+    pub(crate) fn stats(&self) -> HttpCacheStats {
+        HttpCacheStats {
+            entries: self.entries.len(),
+            size_bytes: self.size_bytes,
+            max_size_bytes: self.max_size_bytes,
+            hits: self.hits,
+            misses: self.misses,
+        }
+    }
+}
+
+/// Locks the HTTP cache. The lock is only ever held briefly and never across an await, so a
+/// poisoned lock still holds a consistent cache and is used as is.
+pub(crate) fn lock_http_cache(http_cache: &Mutex<HttpCache>) -> MutexGuard<'_, HttpCache> {
+    http_cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Resolves once aggressive caching is off, or the cache `receiver` was subscribed to is gone.
+pub(crate) async fn aggressive_caching_turned_off(mut receiver: watch::Receiver<bool>) {
+    while *receiver.borrow_and_update() {
+        if receiver.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Keeps aggressive caching on until it is dropped.
+///
+/// Returned by [`DocumentStore::aggressively_cache_for`](crate::DocumentStore::aggressively_cache_for)
+/// and [`RequestExecutor::aggressively_cache_for`](crate::RequestExecutor::aggressively_cache_for).
+/// While several guards are alive, the duration of the most recently created one applies.
+/// Guards can be dropped in any order; aggressive caching is off once all of them are gone.
+#[must_use = "aggressive caching is turned off again as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct AggressiveCacheGuard {
+    http_cache: Arc<Mutex<HttpCache>>,
+    id: u64,
+}
+
+impl AggressiveCacheGuard {
+    pub(crate) fn new(http_cache: Arc<Mutex<HttpCache>>, duration: Duration) -> Self {
+        let id = lock_http_cache(&http_cache).start_aggressive_caching(duration);
+        Self { http_cache, id }
+    }
+}
+
+impl Drop for AggressiveCacheGuard {
+    fn drop(&mut self) {
+        lock_http_cache(&self.http_cache).stop_aggressive_caching(self.id);
+    }
+}
+
+/// A snapshot of a [`RequestExecutor`](crate::RequestExecutor)'s HTTP cache.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct HttpCacheStats {
+    /// Number of responses cached.
+    pub entries: usize,
+    /// Combined size of the cached response bodies.
+    pub size_bytes: usize,
+    pub max_size_bytes: usize,
+    /// GET requests answered from the cache.
+    pub hits: u64,
+    /// GET requests the cache could not answer.
+    pub misses: u64,
+}
+
+impl HttpCacheStats {
+    /// Returns the share of GET requests answered from the cache, between 0 and 1.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / total as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use reqwest::{
+        header::{HeaderMap, HeaderValue},
+        StatusCode,
+    };
+
+    use super::{AggressiveCacheGuard, CachedResponse, HttpCache};
+
+    fn response(size: usize) -> CachedResponse {
+        CachedResponse {
+            etag: HeaderValue::from_static("\"A:1\""),
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from(vec![0; size]),
+        }
+    }
+
+    #[test]
+    fn insert_evicts_least_recently_used_entries() {
+        // Arrange
+        let mut cache = HttpCache::new(100);
+        cache.insert("a".to_string(), response(40), 0);
+        cache.insert("b".to_string(), response(40), 0);
+        cache.hit("a", 0);
+
+        // Act
+        cache.insert("c".to_string(), response(40), 0);
+
+        // Assert
+        assert!(cache.etag_for("a").is_some());
+        assert!(cache.etag_for("b").is_none());
+        assert!(cache.etag_for("c").is_some());
+        assert_eq!(cache.stats().size_bytes, 80);
+    }
+
+    #[test]
+    fn insert_skips_responses_larger_than_the_cache() {
+        // Arrange
+        let mut cache = HttpCache::new(100);
+
+        // Act
+        cache.insert("a".to_string(), response(101), 0);
+
+        // Assert
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn stats_report_hit_rate() {
+        // Arrange
+        let mut cache = HttpCache::new(100);
+        cache.insert("a".to_string(), response(10), 0);
+
+        // Act
+        cache.hit("a", 0);
+        cache.hit("a", 0);
+        cache.hit("a", 0);
+        cache.miss();
+
+        // Assert
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (3, 1));
+        assert_eq!(stats.hit_rate(), 0.75);
+    }
+
+    #[test]
+    fn aggressive_hit_requires_watched_changes_and_current_generation() {
+        // Arrange
+        let mut cache = HttpCache::new(100);
+        cache.insert("a".to_string(), response(10), 0);
+        cache.start_aggressive_caching(Duration::from_secs(60));
+
+        // Act
+        let unwatched = cache.aggressive_hit("a");
+        cache.set_watching_changes(true);
+        let watched = cache.aggressive_hit("a");
+        cache.invalidate();
+        let invalidated = cache.aggressive_hit("a");
+        let generation = cache.generation();
+        cache.hit("a", generation);
+        let revalidated = cache.aggressive_hit("a");
+
+        // Assert
+        assert!(unwatched.is_none());
+        assert!(watched.is_some());
+        assert!(invalidated.is_none());
+        assert!(revalidated.is_some());
+    }
+
+    #[test]
+    fn aggressive_hit_expires_after_duration() {
+        // Arrange
+        let mut cache = HttpCache::new(100);
+        cache.set_watching_changes(true);
+        cache.insert("a".to_string(), response(10), 0);
+        cache.start_aggressive_caching(Duration::ZERO);
+
+        // Act
+        let result = cache.aggressive_hit("a");
+
+        // Assert
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn aggressive_cache_guard_restores_previous_duration_on_drop() {
+        // Arrange
+        let http_cache = Arc::new(Mutex::new(HttpCache::new(100)));
+        let outer = AggressiveCacheGuard::new(http_cache.clone(), Duration::from_secs(60));
+
+        // Act
+        let inner = AggressiveCacheGuard::new(http_cache.clone(), Duration::from_secs(5));
+        let during = http_cache.lock().unwrap().aggressive_cache_duration();
+        drop(inner);
+        let after_inner = http_cache.lock().unwrap().aggressive_cache_duration();
+        drop(outer);
+        let after_outer = http_cache.lock().unwrap().aggressive_cache_duration();
+
+        // Assert
+        assert_eq!(during, Some(Duration::from_secs(5)));
+        assert_eq!(after_inner, Some(Duration::from_secs(60)));
+        assert_eq!(after_outer, None);
+    }
+
+    #[test]
+    fn aggressive_cache_guards_can_be_dropped_out_of_order() {
+        // Arrange
+        let http_cache = Arc::new(Mutex::new(HttpCache::new(100)));
+        let receiver = http_cache.lock().unwrap().subscribe_to_aggressive_caching();
+        let a = AggressiveCacheGuard::new(http_cache.clone(), Duration::from_secs(10));
+        let b = AggressiveCacheGuard::new(http_cache.clone(), Duration::from_secs(1));
+
+        // Act
+        drop(a);
+        let after_a = http_cache.lock().unwrap().aggressive_cache_duration();
+        let on_after_a = *receiver.borrow();
+        drop(b);
+        let after_b = http_cache.lock().unwrap().aggressive_cache_duration();
+        let on_after_b = *receiver.borrow();
+
+        // Assert
+        assert_eq!(after_a, Some(Duration::from_secs(1)));
+        assert!(on_after_a);
+        assert_eq!(after_b, None);
+        assert!(!on_after_b);
+    }
+}
@@ -0,0 +1,473 @@
+//! Builds the HTTP clients executors talk to the servers with.
+
+use std::{collections::HashMap, sync::Arc, time::SystemTime};
+
+use anyhow::Context;
+use reqwest::{Identity, IntoUrl, Request, RequestBuilder, Response, Url};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier},
+    Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName,
+};
+
+use crate::{DnsOverrides, ProxySettings};
+
+use super::RequestExecutorError;
+
+/// The certificates, dns overrides and proxy every HTTP client of an executor is built with.
+#[derive(Clone, Default)]
+pub(crate) struct HttpClientSettings {
+    /// PEM encoded client certificate chain and private key.
+    pub(crate) client_certificate_pem: Option<Vec<u8>>,
+    pub(crate) dns_overrides: DnsOverrides,
+    pub(crate) proxy: Option<ProxySettings>,
+    pub(crate) trust_store: TrustStore,
+}
+
+// Keeps the private key out of logs
+impl std::fmt::Debug for HttpClientSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpClientSettings")
+            .field(
+                "client_certificate_pem",
+                &self.client_certificate_pem.as_ref().map(|_| ".."),
+            )
+            .field("dns_overrides", &self.dns_overrides)
+            .field("proxy", &self.proxy)
+            .field("trust_store", &self.trust_store)
+            .finish()
+    }
+}
+
+/// Which server certificates are trusted, on top of the usual public CAs.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TrustStore {
+    /// DER encoded certificates of CAs to trust, such as an internal CA.
+    ca_certificates: Vec<Vec<u8>>,
+    /// SHA-1 thumbprints of the server certificates to accept. Any certificate is accepted when
+    /// empty.
+    pinned_thumbprints: Vec<[u8; 20]>,
+}
+
+impl TrustStore {
+    /// Reads the certificates out of the PEM encoded CA bundles and parses the thumbprints, which
+    /// are hex encoded like the ones RavenDB Studio shows.
+    pub(crate) fn new<B, T>(ca_bundles: &[B], pinned_thumbprints: &[T]) -> anyhow::Result<Self>
+    where
+        B: AsRef<[u8]>,
+        T: AsRef<str>,
+    {
+        let mut ca_certificates = Vec::new();
+        for bundle in ca_bundles {
+            let certificates = rustls_pemfile::certs(&mut bundle.as_ref())
+                .context("Unable to read the trusted CA bundle")?;
+            if certificates.is_empty() {
+                anyhow::bail!("The trusted CA bundle holds no PEM encoded certificate");
+            }
+            for certificate in &certificates {
+                reqwest::Certificate::from_der(certificate)
+                    .context("Invalid certificate in the trusted CA bundle")?;
+            }
+            ca_certificates.extend(certificates);
+        }
+
+        let pinned_thumbprints = pinned_thumbprints
+            .iter()
+            .map(|thumbprint| parse_thumbprint(thumbprint.as_ref()))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            ca_certificates,
+            pinned_thumbprints,
+        })
+    }
+}
+
+/// An HTTP client with the executor's settings. It keeps a pool of connections, so it is built
+/// once and cloned for every request rather than rebuilt.
+///
+/// reqwest connects to the port of the url even when the host's address is overridden, so
+/// requests to hosts overridden with a port are sent to that port here.
+#[derive(Clone, Debug)]
+pub(crate) struct HttpClient {
+    client: reqwest::Client,
+    override_ports: Arc<HashMap<String, u16>>,
+}
+
+impl HttpClient {
+    fn new(client: reqwest::Client, dns_overrides: &DnsOverrides) -> Self {
+        // Port 0 keeps the port of the url. Addresses of a host share their port, which the
+        // document store validated.
+        let override_ports = dns_overrides
+            .iter()
+            .filter_map(|(host, addresses)| {
+                let port = addresses.first()?.port();
+                (port != 0).then(|| (host.to_ascii_lowercase(), port))
+            })
+            .collect();
+        Self {
+            client,
+            override_ports: Arc::new(override_ports),
+        }
+    }
+
+    pub(crate) async fn execute(&self, mut request: Request) -> reqwest::Result<Response> {
+        self.override_port(request.url_mut());
+        self.client.execute(request).await
+    }
+
+    pub(crate) fn get<U: IntoUrl>(&self, url: U) -> reqwest::Result<RequestBuilder> {
+        let mut url = url.into_url()?;
+        self.override_port(&mut url);
+        Ok(self.client.get(url))
+    }
+
+    fn override_port(&self, url: &mut Url) {
+        let port = url
+            .host_str()
+            .and_then(|host| self.override_ports.get(&host.to_ascii_lowercase()));
+        if let Some(port) = port {
+            let _ = url.set_port(Some(*port));
+        }
+    }
+}
+
+impl From<reqwest::Client> for HttpClient {
+    fn from(client: reqwest::Client) -> Self {
+        Self::new(client, &DnsOverrides::default())
+    }
+}
+
+/// Builds the HTTP client an executor sends all of its requests with.
+///
+/// With `use_compression`, the client decompresses gzip and brotli responses.
+pub(crate) fn build_http_client(
+    settings: &HttpClientSettings,
+    use_compression: bool,
+) -> Result<HttpClient, RequestExecutorError> {
+    let client = http_client_builder(settings, false)?
+        .gzip(use_compression)
+        .brotli(use_compression)
+        .build()
+        .context("Unable to build the HTTP client")?;
+    Ok(HttpClient::new(client, &settings.dns_overrides))
+}
+
+/// Builds a client for connections upgraded to websockets, which HTTP/2 connections can't be.
+pub(crate) fn build_websocket_client(
+    settings: &HttpClientSettings,
+) -> Result<HttpClient, RequestExecutorError> {
+    let client = http_client_builder(settings, true)?
+        .build()
+        .context("Unable to build the HTTP client")?;
+    Ok(HttpClient::new(client, &settings.dns_overrides))
+}
+
+/// Returns a client builder with the executor's certificates, dns overrides and proxy applied.
+fn http_client_builder(
+    settings: &HttpClientSettings,
+    http1_only: bool,
+) -> Result<reqwest::ClientBuilder, RequestExecutorError> {
+    let mut client = reqwest::Client::builder();
+    if http1_only {
+        client = client.http1_only();
+    }
+
+    if settings.trust_store.pinned_thumbprints.is_empty() {
+        if let Some(pem) = &settings.client_certificate_pem {
+            let identity = Identity::from_pem(pem).context("Invalid client certificate")?;
+            client = client.identity(identity).use_rustls_tls();
+        }
+        for certificate in &settings.trust_store.ca_certificates {
+            let certificate = reqwest::Certificate::from_der(certificate)
+                .context("Invalid trusted CA certificate")?;
+            client = client.add_root_certificate(certificate).use_rustls_tls();
+        }
+    } else {
+        // Pinning needs a certificate verifier of our own, which only fits into a TLS
+        // configuration built from scratch
+        client = client.use_preconfigured_tls(pinned_tls_config(settings, http1_only)?);
+    }
+
+    for (domain, addresses) in &settings.dns_overrides {
+        tracing::trace!(
+            "Adding `{}->{:?}` to dns overrides for this executor.",
+            domain,
+            addresses
+        );
+        client = client.resolve_to_addrs(domain.as_str(), addresses);
+    }
+
+    if let Some(proxy) = &settings.proxy {
+        tracing::trace!("Proxy set to `{:?}`", proxy);
+        client = client.proxy(proxy.to_reqwest_proxy()?);
+    } else {
+        tracing::trace!("No proxy defined. Using system settings.");
+    }
+
+    Ok(client)
+}
+
+/// Builds the TLS configuration reqwest would, but with a verifier that also checks the server
+/// certificate's thumbprint against the pinned ones.
+fn pinned_tls_config(
+    settings: &HttpClientSettings,
+    http1_only: bool,
+) -> Result<ClientConfig, RequestExecutorError> {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|trust_anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            trust_anchor.subject,
+            trust_anchor.spki,
+            trust_anchor.name_constraints,
+        )
+    }));
+    for certificate in &settings.trust_store.ca_certificates {
+        roots
+            .add(&Certificate(certificate.clone()))
+            .context("Invalid trusted CA certificate")?;
+    }
+
+    let builder = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots.clone());
+    let mut config = match &settings.client_certificate_pem {
+        Some(pem) => {
+            let (chain, key) = read_client_certificate(pem)?;
+            builder
+                .with_client_auth_cert(chain, key)
+                .context("Invalid client certificate")?
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(PinnedCertificateVerifier {
+            inner: WebPkiVerifier::new(roots, None),
+            pinned_thumbprints: settings.trust_store.pinned_thumbprints.clone(),
+        }));
+    config.alpn_protocols = if http1_only {
+        vec![b"http/1.1".to_vec()]
+    } else {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    };
+    Ok(config)
+}
+
+/// Splits the PEM encoded client certificate into its chain and private key.
+fn read_client_certificate(pem: &[u8]) -> anyhow::Result<(Vec<Certificate>, rustls::PrivateKey)> {
+    let mut chain = Vec::new();
+    let mut key = None;
+    for item in rustls_pemfile::read_all(&mut &pem[..]).context("Invalid client certificate")? {
+        match item {
+            rustls_pemfile::Item::X509Certificate(der) => chain.push(Certificate(der)),
+            rustls_pemfile::Item::RSAKey(der)
+            | rustls_pemfile::Item::PKCS8Key(der)
+            | rustls_pemfile::Item::ECKey(der) => key = Some(rustls::PrivateKey(der)),
+            _ => {}
+        }
+    }
+    let key = key.context("The client certificate has no private key")?;
+    Ok((chain, key))
+}
+
+/// Verifies server certificates like any other client, then rejects those whose thumbprint
+/// isn't pinned.
+struct PinnedCertificateVerifier {
+    inner: WebPkiVerifier,
+    pinned_thumbprints: Vec<[u8; 20]>,
+}
+
+impl ServerCertVerifier for PinnedCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let thumbprint = sha1_smol::Sha1::from(&end_entity.0).digest().bytes();
+        if !self.pinned_thumbprints.contains(&thumbprint) {
+            tracing::error!(
+                "Rejected server certificate with unpinned thumbprint `{}`",
+                format_thumbprint(&thumbprint)
+            );
+            return Err(rustls::Error::General(
+                "The server certificate's thumbprint is not pinned".to_string(),
+            ));
+        }
+
+        self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+}
+
+/// Parses a hex encoded SHA-1 thumbprint. Colons and spaces between the bytes are ignored.
+fn parse_thumbprint(thumbprint: &str) -> anyhow::Result<[u8; 20]> {
+    let hex = thumbprint
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect::<String>();
+    let invalid = || anyhow::anyhow!("`{}` is not a SHA-1 certificate thumbprint", thumbprint);
+    if hex.len() != 40 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+
+    let mut bytes = [0; 20];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+fn format_thumbprint(thumbprint: &[u8]) -> String {
+    thumbprint
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use rustls::{
+        client::{ServerCertVerifier, WebPkiVerifier},
+        Certificate, RootCertStore, ServerName,
+    };
+
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::ProxySettings;
+
+    use super::{
+        build_http_client, parse_thumbprint, HttpClientSettings, PinnedCertificateVerifier,
+        TrustStore,
+    };
+
+    const DEV_CERTIFICATE: &[u8] = include_bytes!("../../../ravendb-client_dev_cert.pem");
+
+    #[tokio::test]
+    async fn build_http_client_sends_to_dns_override_port() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let settings = HttpClientSettings {
+            dns_overrides: [("raven1".to_string(), vec![*server.address()])].into(),
+            ..Default::default()
+        };
+        let client = build_http_client(&settings, false).unwrap();
+
+        // Act
+        let response = client
+            .get("http://raven1:8080/build/version")
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status(), 200);
+    }
+
+    #[test]
+    fn build_http_client_rejects_invalid_proxy() {
+        // Arrange
+        let settings = HttpClientSettings {
+            proxy: Some(ProxySettings::new("not a proxy")),
+            ..Default::default()
+        };
+
+        // Act
+        let result = build_http_client(&settings, true);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn build_http_client_accepts_trusted_ca_and_pinned_thumbprint() {
+        // Arrange
+        let settings = HttpClientSettings {
+            client_certificate_pem: Some(DEV_CERTIFICATE.to_vec()),
+            trust_store: TrustStore::new(
+                &[DEV_CERTIFICATE],
+                &["A9:4A:8F:E5:CC:B1:9B:A6:1C:4C:08:73:D3:91:E9:87:98:2F:BB:D3"],
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+
+        // Act
+        let result = build_http_client(&settings, true);
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn trust_store_rejects_bundle_without_certificates() {
+        // Act
+        let result = TrustStore::new(&[b"not a certificate"], &[] as &[&str]);
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parse_thumbprint_accepts_studio_format_and_rejects_garbage() {
+        // Act
+        let plain = parse_thumbprint("a94a8fe5ccb19ba61c4c0873d391e987982fbbd3").unwrap();
+        let separated =
+            parse_thumbprint("A9:4A:8F:E5:CC:B1:9B:A6:1C:4C:08:73:D3:91:E9:87:98:2F:BB:D3")
+                .unwrap();
+
+        // Assert
+        assert_eq!(plain, separated);
+        assert_eq!(plain[0], 0xA9);
+        assert!(parse_thumbprint("A94A").is_err());
+        assert!(parse_thumbprint("zz4a8fe5ccb19ba61c4c0873d391e987982fbbd3").is_err());
+    }
+
+    #[test]
+    fn pinned_certificate_verifier_rejects_unpinned_certificate() {
+        // Arrange
+        let certificate = rustls_pemfile::certs(&mut &DEV_CERTIFICATE[..]).unwrap()[0].clone();
+        let verifier = PinnedCertificateVerifier {
+            inner: WebPkiVerifier::new(RootCertStore::empty(), None),
+            pinned_thumbprints: vec![[0; 20]],
+        };
+
+        // Act
+        let result = verifier.verify_server_cert(
+            &Certificate(certificate),
+            &[],
+            &ServerName::try_from("localhost").unwrap(),
+            &mut std::iter::empty(),
+            &[],
+            SystemTime::now(),
+        );
+
+        // Assert
+        assert!(
+            matches!(result, Err(rustls::Error::General(message)) if message.contains("not pinned"))
+        );
+    }
+}
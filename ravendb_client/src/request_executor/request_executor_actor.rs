@@ -1,59 +1,105 @@
 use std::{
-    collections::{HashMap, HashSet},
-    net::SocketAddr,
+    collections::HashSet,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use rand::{seq::IteratorRandom, thread_rng};
-use reqwest::{header::HeaderValue, Identity, Url};
-use tokio::sync::mpsc;
+use rand::Rng;
+use reqwest::{
+    header::{HeaderValue, ETAG, IF_NONE_MATCH},
+    Method, StatusCode, Url,
+};
+use tokio::{sync::mpsc, task::JoinHandle, time::Instant};
 use tracing::{instrument, Span};
 use uuid::Uuid;
 
 use crate::{
-    database_topology::DatabaseTopology, document_conventions::DocumentConventions,
-    node_selector::NodeSelector, raven_command::RavenCommand, server_node::ServerNode,
-    DnsOverrides,
+    client_configuration::{ClientConfiguration, GetClientConfigurationResult},
+    cluster_topology::ClusterTopology,
+    database_topology::DatabaseTopology,
+    document_conventions::{
+        DocumentConventions, HttpCompressionAlgorithm, LoadBalanceBehavior, ReadBalanceBehavior,
+    },
+    node_selector::NodeSelector,
+    raven_command::{
+        GetClientConfigurationCommand, GetClusterTopologyCommand, GetDatabaseTopologyCommand,
+        RavenCommand,
+    },
+    ravendb_error::{is_node_failure_status, RavenDbError},
+    server_node::ServerNode,
+    SessionInfo,
+};
+
+use super::{
+    changes_listener, compression,
+    http_cache::{aggressive_caching_turned_off, lock_http_cache, CachedResponse, HttpCache},
+    http_client::{build_http_client, build_websocket_client, HttpClient, HttpClientSettings},
+    topology_cache, RequestExecutorError, RequestExecutorMessage,
 };
 
-use super::{RequestExecutorError, RequestExecutorMessage};
+/// How long to wait before watching the changes again after the connection was lost.
+const CHANGES_RECONNECT_DELAY: Duration = Duration::from_secs(5);
 
 pub struct RequestExecutorActor {
     /// Allows the server to warn if [`DocumentStore`] is being recreated too many times
     /// instead of once per application. RequestExecutor should be cached and reused, so
     /// this shouldn't change after initialization.
     application_id: Uuid,
+    /// Client settings pushed by the server. These take precedence over `conventions`.
+    client_configuration: Option<ClientConfiguration>,
+    /// Version of the client configuration last received, sent along with every request so
+    /// the server can tell when it is out of date.
+    client_configuration_etag: i64,
+    /// Handle to a running client configuration refresh, used to avoid starting a second one.
+    client_configuration_updater: Option<JoinHandle<()>>,
+    /// Handle to the task watching the database's changes for aggressive caching.
+    changes_listener: Option<JoinHandle<()>>,
+    cluster_topology: Option<ClusterTopology>,
+    /// Handle to a running cluster topology refresh, used to avoid starting a second one.
+    cluster_topology_updater: Option<JoinHandle<()>>,
     conventions: DocumentConventions,
     database: String,
     database_topology: Option<DatabaseTopology>,
-    dns_overrides: DnsOverrides,
-    identity: Option<Identity>,
+    /// Set in single node mode when the server's client configuration should be ignored too.
+    disable_client_configuration_updates: bool,
+    /// Set in single node mode. The topology is never downloaded and stays the one node given.
+    disable_topology_updates: bool,
+    /// Shared with the tasks sending requests, which revalidate and update it.
+    http_cache: Arc<Mutex<HttpCache>>,
+    /// Certificates, dns overrides and proxy the HTTP clients are built with.
+    http_client_settings: HttpClientSettings,
+    /// The urls the executor was created with. These identify the cluster in the topology cache.
+    initial_urls: Vec<Url>,
     last_known_urls: Vec<Url>,
     node_selector: Option<NodeSelector>,
-    proxy_address: Option<String>,
     receiver: mpsc::Receiver<RequestExecutorMessage>,
     receiver_internal: mpsc::Receiver<RequestExecutorMessage>,
     /// Cached http client. Clone this into tokio::spawn() for each request, it's cheap.
-    reqwest_client: reqwest::Client,
+    reqwest_client: HttpClient,
     sender_internal: mpsc::Sender<RequestExecutorMessage>,
     /// Whether or not to run speed tests
     run_speed_test: bool,
-    /// Holds the topology
-    topology: Option<DatabaseTopology>,
+    /// Handle to a running topology refresh, used to avoid starting a second one.
+    topology_updater: Option<JoinHandle<()>>,
 }
 
 impl RequestExecutorActor {
     pub(crate) fn new(
         receiver: mpsc::Receiver<RequestExecutorMessage>,
         database: String,
-        identity: Option<Identity>,
         initial_urls: Vec<Url>,
-        dns_overrides: DnsOverrides,
-        proxy_address: Option<String>,
+        http_client_settings: HttpClientSettings,
         conventions: DocumentConventions,
-    ) -> Self {
+    ) -> Result<Self, RequestExecutorError> {
         // Reqwest client maintains an internal connection pool. Reuse it so long as this
         // RequestExecutor lives.
-        let reqwest_client = reqwest::Client::new();
+        let reqwest_client =
+            build_http_client(&http_client_settings, conventions.use_compression())?;
+
+        let http_cache = Arc::new(Mutex::new(HttpCache::new(
+            conventions.max_http_cache_size(),
+        )));
 
         // Create internal messaging channel
         let (sender_internal, receiver_internal) = mpsc::channel(10);
@@ -61,30 +107,73 @@ impl RequestExecutorActor {
         // Get the initial topology
         //let database_topology = self.initial_topology_update();
 
-        // if let Some(identity) = client_identity.clone() {
-        //                 client = client.identity(identity).use_rustls_tls();
-        //             }
-
         //TODO: Kick off first topology update
 
-        Self {
+        Ok(Self {
             application_id: Uuid::new_v4(),
+            client_configuration: None,
+            client_configuration_etag: 0,
+            client_configuration_updater: None,
+            changes_listener: None,
+            cluster_topology: None,
+            cluster_topology_updater: None,
             conventions,
             database,
             database_topology: None,
-            dns_overrides,
-            identity: Option::default(),
-            last_known_urls: Vec::default(),
+            disable_client_configuration_updates: false,
+            disable_topology_updates: false,
+            http_cache,
+            http_client_settings,
+            initial_urls: initial_urls.clone(),
+            last_known_urls: initial_urls,
             node_selector: Option::default(),
-            proxy_address,
             receiver,
             receiver_internal,
             reqwest_client,
             sender_internal,
             run_speed_test: false,
-            topology: None,
-        }
+            topology_updater: None,
+        })
+    }
+    /// Returns the HTTP cache, shared with the handle so it can turn on aggressive caching.
+    pub(crate) fn http_cache(&self) -> Arc<Mutex<HttpCache>> {
+        self.http_cache.clone()
     }
+
+    /// The identifier sent along with topology requests, if the conventions allow it.
+    fn application_identifier(&self) -> Option<Uuid> {
+        self.conventions
+            .send_application_identified()
+            .then_some(self.application_id)
+    }
+
+    /// Switches the executor to single node mode, talking only to the first initial url.
+    ///
+    /// The topology is made of that one node and is never updated. The client configuration is
+    /// still downloaded from it unless `disable_client_configuration_updates` is set.
+    pub(crate) fn with_single_node(mut self, disable_client_configuration_updates: bool) -> Self {
+        let nodes = self
+            .initial_urls
+            .first()
+            .map(|url| {
+                let mut server_node = ServerNode::new(url.clone(), self.database.clone());
+                server_node.cluster_tag = "!".to_string();
+                server_node
+            })
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let topology = DatabaseTopology {
+            nodes,
+            ..Default::default()
+        };
+
+        self.node_selector = Some(NodeSelector::new(Some(topology.clone())));
+        self.database_topology = Some(topology);
+        self.disable_topology_updates = true;
+        self.disable_client_configuration_updates = disable_client_configuration_updates;
+        self
+    }
+
     async fn handle_message(&mut self, msg: RequestExecutorMessage) {
         // Apply a correlation id to all child spans of this message handler
         Span::current().record("correlation_id", Uuid::new_v4().to_string());
@@ -92,161 +181,567 @@ impl RequestExecutorActor {
             RequestExecutorMessage::ExecuteRavenCommand {
                 respond_to,
                 command,
+                session_info,
             } => {
                 //TODO: Nuke this and wait for topology to be done, maybe.
                 let Some(topology) = self.database_topology.clone() else {
                     // Database doesn't exist yet so send the caller a message to tell them
-                    let _ = respond_to.send(Err(RequestExecutorError::UnexpectedError(anyhow::anyhow!("Unable to get topology, initial update not yet finished"))));
+                    let _ = respond_to.send(Err(RequestExecutorError::UnexpectedError(
+                        anyhow::anyhow!("Unable to get topology, initial update not yet finished"),
+                    )));
                     return;
                 };
 
-                let dns_overrides = self.dns_overrides.clone();
-                let identity = self.identity.clone();
-                let proxy_address = self.proxy_address.clone();
-                let topology_etag = topology.etag;
-                let sender_internal = self.sender_internal.clone();
+                // Route the command to the node the load balancing conventions pick for it
+                let Some(node) =
+                    self.choose_node_for_request(command.is_read_request(), session_info.as_ref())
+                else {
+                    let _ = respond_to.send(Err(RequestExecutorError::UnexpectedError(
+                        anyhow::anyhow!("The topology has no nodes to send the command to"),
+                    )));
+                    return;
+                };
+                // If the chosen node fails, the rest of the topology is tried in turn
+                let nodes = match self.node_selector.as_ref() {
+                    Some(node_selector) => node_selector.get_failover_nodes(&node),
+                    None => vec![node],
+                };
+
+                let parameters = ExecuteParameters {
+                    timeout: command.timeout().or(self.conventions.request_timeout()),
+                    compression: self
+                        .conventions
+                        .use_compression()
+                        .then(|| self.conventions.http_compression_algorithm()),
+                    command,
+                    nodes,
+                    client: self.reqwest_client.clone(),
+                    http_cache: self.http_cache.clone(),
+                    topology_etag: topology.etag,
+                    client_configuration_etag: self.client_configuration_etag,
+                    sender_internal: self.sender_internal.clone(),
+                };
 
                 // Spawn a task to do the request
                 tokio::spawn(async move {
-                    let result = send_raven_command_request_to_server(
-                        identity.clone(),
-                        dns_overrides.clone(),
-                        proxy_address.clone(),
-                        command,
-                        topology_etag,
-                    )
-                    .await;
-
-                    if let Ok(response) = &result {
-                        if let Some(value) =
-                            response.headers().get("Refresh-Topology".to_lowercase())
-                        {
-                            if value.to_str().unwrap_or("false") == "true" {
-                                if let Err(e) = sender_internal
-                                    .send(RequestExecutorMessage::UpdateTopology)
-                                    .await
-                                {
-                                    tracing::error!(
-                                        "Could not send internal message to request topology update. Caused by: {}",
-                                         e
-                                    );
-                                }
-                            }
+                    let mut respond_to = respond_to;
+                    tokio::select! {
+                        // The caller dropped its future, so nobody is waiting for the result
+                        _ = respond_to.closed() => {
+                            tracing::debug!("Caller stopped waiting for the command. Canceling it.");
+                        }
+                        result = execute_with_failover(parameters) => {
+                            let _ = respond_to.send(result);
                         }
                     }
-
-                    // Send the result back to the caller
-                    //let _ = respond_to.send(result);
                 });
             }
-            RequestExecutorMessage::InitialUpdateTopology { initial_urls } => {
-                //TODO: Change this to handle subsequent topology updates, or consider handling both initial and subsequent in same fn
-                // let result = self
-                //     .initial_update_topology(initial_urls, self.application_id)
-                //     .await;
-                // if let Err(e) = result {
-                //     tracing::error!(
-                //         "An error occurred while running the initial topology update. Caused by: {:?}",
-                //         e
-                //     );
-                // }
-                unimplemented!();
+            RequestExecutorMessage::InitialUpdateTopology {
+                initial_urls,
+                respond_to,
+            } => {
+                // The handle waits on this before it is returned to anyone, so nothing else is
+                // queued behind this update and it is fine to await it here.
+                let result = initial_update_topology(
+                    initial_urls,
+                    self.database.clone(),
+                    self.application_identifier(),
+                    self.reqwest_client.clone(),
+                    self.conventions
+                        .topology_cache_location()
+                        .map(|location| location.to_path_buf()),
+                )
+                .await;
+
+                let result = match result {
+                    Ok(topology) => {
+                        self.on_topology_updated(topology);
+                        Ok(())
+                    }
+                    Err(errors) => {
+                        tracing::error!(
+                            "An error occurred while running the initial topology update. Caused by: {:?}",
+                            errors
+                        );
+                        Err(RequestExecutorError::InitialTopologyUpdateFailed(errors))
+                    }
+                };
+                let _ = respond_to.send(result);
+            }
+            RequestExecutorMessage::GetConventions { respond_to } => {
+                let conventions = self
+                    .conventions
+                    .with_client_configuration(self.client_configuration.as_ref());
+                let _ = respond_to.send(conventions);
+            }
+            RequestExecutorMessage::GetHttpCacheStats { respond_to } => {
+                let stats = lock_http_cache(&self.http_cache).stats();
+                let _ = respond_to.send(stats);
+            }
+            RequestExecutorMessage::ReplaceClientCertificate {
+                client_certificate_pem,
+                respond_to,
+            } => {
+                let result = self.replace_client_certificate(client_certificate_pem);
+                let _ = respond_to.send(result);
+            }
+            RequestExecutorMessage::WatchChanges => {
+                self.watch_changes();
+            }
+            RequestExecutorMessage::NodeFailed { node } => {
+                if let Some(node_selector) = self.node_selector.as_mut() {
+                    node_selector.record_failure(&node);
+                }
+            }
+            RequestExecutorMessage::RecordResponseTime {
+                node,
+                response_time_ms,
+            } => {
+                if let Some(node_selector) = self.node_selector.as_mut() {
+                    node_selector.record_response_time(&node, response_time_ms);
+                }
+            }
+            RequestExecutorMessage::UpdateClusterTopology => {
+                self.update_cluster_topology();
+            }
+            RequestExecutorMessage::ClusterTopologyUpdated { topology } => {
+                self.on_cluster_topology_updated(topology);
             }
             RequestExecutorMessage::UpdateTopology => {
-                todo!();
+                self.update_topology();
             }
             RequestExecutorMessage::TopologyUpdated { topology } => {
-                todo!();
+                self.on_topology_updated(topology);
+            }
+            RequestExecutorMessage::UpdateClientConfiguration => {
+                self.update_client_configuration();
+            }
+            RequestExecutorMessage::ClientConfigurationUpdated { result } => {
+                self.on_client_configuration_updated(result);
             }
         }
     }
 
-    async fn wait_for_initial_topology(
-        &self,
-        initial_urls: Vec<Url>,
-        application_id: Uuid,
-    ) -> Result<(), Vec<(Url, RequestExecutorError)>> {
+    /// Swaps the HTTP client for one presenting the new client certificate.
+    ///
+    /// Requests already sent hold a clone of the previous client and finish on it. An open
+    /// Changes API connection is kept, and picks up the new certificate when it reconnects.
+    fn replace_client_certificate(
+        &mut self,
+        client_certificate_pem: Option<Vec<u8>>,
+    ) -> Result<(), RequestExecutorError> {
+        let http_client_settings = HttpClientSettings {
+            client_certificate_pem,
+            ..self.http_client_settings.clone()
+        };
+        self.reqwest_client =
+            build_http_client(&http_client_settings, self.conventions.use_compression())?;
+        self.http_client_settings = http_client_settings;
+        tracing::info!(
+            "Replaced the client certificate of the executor for database `{}`",
+            self.database
+        );
         Ok(())
     }
 
-    async fn get_topology_from_node(
-        parameters: UpdateTopologyParameters,
-    ) -> Result<(), RequestExecutorError> {
-        todo!()
+    /// Starts a background refresh of the cluster topology, unless one is already running.
+    #[instrument(level = "debug", skip(self))]
+    fn update_cluster_topology(&mut self) {
+        if self.disable_topology_updates {
+            tracing::debug!(
+                "Topology updates are disabled. Ignoring cluster topology update request."
+            );
+            return;
+        }
+
+        if is_running(&self.cluster_topology_updater) {
+            tracing::debug!(
+                "Cluster topology update already running. Canceling to avoid duplication of effort."
+            );
+            return;
+        }
+
+        let Some(server_node) = self.get_preferred_node_or_initial_url() else {
+            tracing::warn!("No node is known to request a cluster topology update from.");
+            return;
+        };
+
+        let client = self.reqwest_client.clone();
+        let topology_etag = self.database_topology.as_ref().map_or(0, |t| t.etag);
+        let client_configuration_etag = self.client_configuration_etag;
+        let sender_internal = self.sender_internal.clone();
+
+        self.cluster_topology_updater = Some(tokio::spawn(async move {
+            let result = async {
+                let command = GetClusterTopologyCommand;
+                let response = send_raven_command_request_to_server(
+                    &client,
+                    command.create_request(&server_node)?,
+                    topology_etag,
+                    client_configuration_etag,
+                )
+                .await?;
+                command.parse_response(response).await
+            }
+            .await;
+
+            match result {
+                Ok(info) => {
+                    if let Err(e) = sender_internal
+                        .send(RequestExecutorMessage::ClusterTopologyUpdated {
+                            topology: info.topology,
+                        })
+                        .await
+                    {
+                        tracing::error!(
+                            "Could not send internal message with the updated cluster topology. Caused by: {}",
+                            e
+                        );
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "There was an error updating the cluster topology. Caused by: {}",
+                        e
+                    );
+                }
+            }
+        }));
     }
 
-    fn get_topology_nodes(&self) -> Option<HashSet<ServerNode>> {
-        if let Some(topology) = self.get_topology() {
-            Some(topology.nodes)
-        } else {
-            None
+    /// Replaces the current cluster topology with the downloaded one if it is newer.
+    #[instrument(level = "debug", skip(self, topology))]
+    fn on_cluster_topology_updated(&mut self, topology: ClusterTopology) {
+        if let Some(current) = &self.cluster_topology {
+            if topology.etag <= current.etag {
+                tracing::debug!(
+                    "Downloaded cluster topology etag {} is not newer than {}. Ignoring it.",
+                    topology.etag,
+                    current.etag
+                );
+                return;
+            }
+        }
+
+        tracing::info!("Cluster topology updated to etag {}", topology.etag);
+        if let Some(cache_location) = self.conventions.topology_cache_location() {
+            tokio::spawn(topology_cache::try_save_cluster_topology(
+                cache_location.to_path_buf(),
+                self.initial_urls.clone(),
+                topology.clone(),
+            ));
         }
+        self.cluster_topology = Some(topology);
     }
 
-    fn get_topology(&self) -> Option<DatabaseTopology> {
-        self.topology.clone()
+    /// Starts a background refresh of the database topology.
+    ///
+    /// Timers and `Refresh-Topology` response headers can ask for a refresh while one is still in
+    /// flight, so a new one is only started once the previous one has finished.
+    #[instrument(level = "debug", skip(self))]
+    fn update_topology(&mut self) {
+        if self.disable_topology_updates {
+            tracing::debug!("Topology updates are disabled. Ignoring topology update request.");
+            return;
+        }
+
+        if is_running(&self.topology_updater) {
+            tracing::debug!(
+                "Topology update already running. Canceling to avoid duplication of effort."
+            );
+            return;
+        }
+
+        let Some(server_node) = self.get_preferred_node_or_initial_url() else {
+            tracing::warn!("No node is known to request a topology update from.");
+            return;
+        };
+
+        let parameters = UpdateTopologyParameters {
+            server_node,
+            application_id: self.application_identifier(),
+            client: self.reqwest_client.clone(),
+        };
+        let sender_internal = self.sender_internal.clone();
+
+        self.topology_updater = Some(tokio::spawn(async move {
+            match update_topology_async(parameters).await {
+                Ok(topology) => {
+                    if let Err(e) = sender_internal
+                        .send(RequestExecutorMessage::TopologyUpdated { topology })
+                        .await
+                    {
+                        tracing::error!(
+                            "Could not send internal message with the updated topology. Caused by: {}",
+                            e
+                        );
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("There was an error updating the topology. Caused by: {}", e);
+                }
+            }
+        }));
     }
 
-    /// Returns the fastest node available if one exists.
-    fn get_fastest_node(&self) -> Option<ServerNode> {
-        // TODO: actually return the fastest node
-        // For now just return the first node
-        self.get_preferred_node()
+    /// Replaces the current topology with the downloaded one if it is newer.
+    #[instrument(level = "debug", skip(self, topology))]
+    fn on_topology_updated(&mut self, topology: DatabaseTopology) {
+        if let Some(current) = &self.database_topology {
+            // A topology with an etag of 0 was never confirmed by a server, so replace it.
+            if current.etag != 0 && topology.etag <= current.etag {
+                tracing::debug!(
+                    "Downloaded topology etag {} is not newer than {}. Ignoring it.",
+                    topology.etag,
+                    current.etag
+                );
+                return;
+            }
+        }
+
+        tracing::info!("Topology updated to etag {}", topology.etag);
+        if let Some(cache_location) = self.conventions.topology_cache_location() {
+            tokio::spawn(topology_cache::try_save_database_topology(
+                cache_location.to_path_buf(),
+                self.initial_urls.clone(),
+                self.database.clone(),
+                topology.clone(),
+            ));
+        }
+        self.last_known_urls = topology.nodes.iter().map(|node| node.url.clone()).collect();
+        self.node_selector = Some(NodeSelector::new(Some(topology.clone())));
+        self.database_topology = Some(topology);
     }
 
-    /// Returns a specific node for the given session id.
-    fn get_node_by_session_id(&self, session_id: i32) -> Option<ServerNode> {
-        // TODO: actually return the session_id node.
-        // For now just return preferred node
-        self.get_preferred_node()
+    /// Starts a background refresh of the client configuration, unless one is already running.
+    #[instrument(level = "debug", skip(self))]
+    fn update_client_configuration(&mut self) {
+        if self.disable_client_configuration_updates {
+            tracing::debug!(
+                "Client configuration updates are disabled. Ignoring client configuration update request."
+            );
+            return;
+        }
+
+        if is_running(&self.client_configuration_updater) {
+            tracing::debug!(
+                "Client configuration update already running. Canceling to avoid duplication of effort."
+            );
+            return;
+        }
+
+        let Some(server_node) = self.get_preferred_node_or_initial_url() else {
+            tracing::warn!("No node is known to request the client configuration from.");
+            return;
+        };
+
+        let client = self.reqwest_client.clone();
+        let topology_etag = self.database_topology.as_ref().map_or(0, |t| t.etag);
+        let client_configuration_etag = self.client_configuration_etag;
+        let sender_internal = self.sender_internal.clone();
+
+        self.client_configuration_updater = Some(tokio::spawn(async move {
+            let result = async {
+                let command = GetClientConfigurationCommand;
+                let response = send_raven_command_request_to_server(
+                    &client,
+                    command.create_request(&server_node)?,
+                    topology_etag,
+                    client_configuration_etag,
+                )
+                .await?;
+                command.parse_response(response).await
+            }
+            .await;
+
+            match result {
+                Ok(result) => {
+                    if let Err(e) = sender_internal
+                        .send(RequestExecutorMessage::ClientConfigurationUpdated { result })
+                        .await
+                    {
+                        tracing::error!(
+                            "Could not send internal message with the updated client configuration. Caused by: {}",
+                            e
+                        );
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "There was an error updating the client configuration. Caused by: {}",
+                        e
+                    );
+                }
+            }
+        }));
     }
 
-    /// Returns the currently preferred node.
-    /// Right now this looks for the first node with 0 failures and returns it.
-    /// On the off chance all nodes have failures, it returns a random node.
-    fn get_preferred_node(&self) -> Option<ServerNode> {
-        let x = self.topology.as_ref().and_then(|topology| {
-            topology
-                .node_failures
-                .iter()
-                .find(|(_, count)| **count == 0)
-                .map(|(node, _)| node.clone())
-        });
+    /// Stores the downloaded client configuration if it is newer than the current one.
+    #[instrument(level = "debug", skip(self, result))]
+    fn on_client_configuration_updated(&mut self, result: GetClientConfigurationResult) {
+        if result.etag <= self.client_configuration_etag {
+            tracing::debug!(
+                "Downloaded client configuration etag {} is not newer than {}. Ignoring it.",
+                result.etag,
+                self.client_configuration_etag
+            );
+            return;
+        }
 
-        if x.is_some() {
-            return x;
+        tracing::info!("Client configuration updated to etag {}", result.etag);
+        self.client_configuration_etag = result.etag;
+        self.client_configuration = result.configuration;
+    }
+
+    /// Starts watching the database's changes on the preferred node, so aggressively cached
+    /// responses are dropped once they are stale.
+    ///
+    /// The watch is restarted after the connection is lost for as long as aggressive caching
+    /// stays on, and the connection is closed as soon as it is turned off.
+    #[instrument(level = "debug", skip(self))]
+    fn watch_changes(&mut self) {
+        if is_running(&self.changes_listener) {
+            tracing::debug!("Already watching changes. Canceling to avoid duplication of effort.");
+            return;
         }
 
-        // If all nodes are marked with failures, just select one at random. This may still
-        // be `None` if the topology is empty.
-        // NOTE: JVM version rotates through an index but this lib uses a HashMap to store
-        // the nodes and ordering is irrelevant, so a random choice makes more sense.
-        self.select_random_node()
+        let aggressive_caching = {
+            let http_cache = lock_http_cache(&self.http_cache);
+            if !http_cache.is_enabled() || http_cache.aggressive_cache_duration().is_none() {
+                tracing::debug!("Aggressive caching is off. Not watching changes.");
+                return;
+            }
+            // Subscribed under the same lock, so turning it off right after is still noticed
+            http_cache.subscribe_to_aggressive_caching()
+        };
+
+        let Some(server_node) = self.get_preferred_node_or_initial_url() else {
+            tracing::warn!("No node is known to watch changes on.");
+            return;
+        };
+
+        // Connections of the executor's client may be HTTP/2, which can't become websockets
+        let client = match build_websocket_client(&self.http_client_settings) {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::error!(
+                    "Unable to build the client to watch changes with. Caused by: {}",
+                    e
+                );
+                return;
+            }
+        };
+        let http_cache = self.http_cache.clone();
+        let sender_internal = self.sender_internal.clone();
+
+        self.changes_listener = Some(tokio::spawn(async move {
+            tokio::select! {
+                // The actor is gone, so nobody uses the cache anymore
+                () = sender_internal.closed() => return,
+                () = aggressive_caching_turned_off(aggressive_caching) => {
+                    tracing::debug!("Aggressive caching turned off. Stopped watching changes.");
+                    let mut http_cache = lock_http_cache(&http_cache);
+                    http_cache.set_watching_changes(false);
+                    http_cache.invalidate();
+                    return;
+                }
+                result = changes_listener::watch_changes(&client, &server_node, &http_cache) => {
+                    if let Err(e) = result {
+                        tracing::warn!("Stopped watching changes. Caused by: {:?}", e);
+                    }
+                }
+            }
+
+            // Sent from a separate task, so this one has finished by the time the actor
+            // checks whether it is still running
+            tokio::spawn(async move {
+                tokio::time::sleep(CHANGES_RECONNECT_DELAY).await;
+                let _ = sender_internal
+                    .send(RequestExecutorMessage::WatchChanges)
+                    .await;
+            });
+        }));
     }
 
-    /// Returns the requested node by node tag.
-    fn get_requested_node(&self, tag: String) -> Option<ServerNode> {
-        // TODO: actually return the fastest node
-        // For now just return the first node
-        self.get_preferred_node()
+    /// Returns the preferred node of the current topology or, if there is no topology yet, a
+    /// node made from the first of the last known urls or cluster members.
+    fn get_preferred_node_or_initial_url(&self) -> Option<ServerNode> {
+        self.node_selector
+            .as_ref()
+            .and_then(|node_selector| node_selector.get_preferred_node())
+            .or_else(|| {
+                self.last_known_urls
+                    .first()
+                    .or_else(|| {
+                        self.cluster_topology
+                            .as_ref()
+                            .and_then(|topology| topology.members.values().next())
+                    })
+                    .map(|url| ServerNode::new(url.clone(), self.database.clone()))
+            })
     }
 
-    /// Returns a random node if all are faulted.
-    fn select_random_node(&self) -> Option<ServerNode> {
-        if let Some(topology) = &self.topology {
-            let mut rng = thread_rng();
-            topology.nodes.iter().choose(&mut rng).cloned()
+    fn get_topology_nodes(&self) -> Option<HashSet<ServerNode>> {
+        if let Some(topology) = self.get_topology() {
+            Some(topology.nodes)
         } else {
             None
         }
     }
+
+    fn get_topology(&self) -> Option<DatabaseTopology> {
+        self.database_topology.clone()
+    }
+
+    /// Picks the node a request should be sent to, according to the conventions as overridden
+    /// by the server's client configuration.
+    ///
+    /// When the load balance behavior is [`LoadBalanceBehavior::UseSessionContext`] and the
+    /// session set a context, the request goes to the node that context maps to. Otherwise
+    /// writes go to the preferred node and reads are spread by the read balance behavior.
+    fn choose_node_for_request(
+        &self,
+        is_read_request: bool,
+        session_info: Option<&SessionInfo>,
+    ) -> Option<ServerNode> {
+        let node_selector = self.node_selector.as_ref()?;
+        let conventions = self
+            .conventions
+            .with_client_configuration(self.client_configuration.as_ref());
+
+        if conventions.load_balance_behavior() == LoadBalanceBehavior::UseSessionContext {
+            let seed = conventions.load_balancer_context_seed();
+            if let Some(session_id) = session_info.and_then(|info| info.context_session_id(seed)) {
+                return node_selector.get_node_by_session_id(session_id);
+            }
+        }
+
+        if !is_read_request {
+            return node_selector.get_preferred_node();
+        }
+
+        match conventions.read_balance_behavior() {
+            ReadBalanceBehavior::None => node_selector.get_preferred_node(),
+            ReadBalanceBehavior::RoundRobin => {
+                // Requests made outside of a session have nothing to stick to, so they are
+                // scattered randomly instead.
+                let session_id = session_info
+                    .map(|info| info.session_id())
+                    .unwrap_or_else(rand::random);
+                node_selector.get_node_by_session_id(session_id)
+            }
+            ReadBalanceBehavior::FastestNode => node_selector.get_fastest_node(),
+        }
+    }
 }
 
-#[instrument(level = "debug")]
+#[instrument(level = "debug", skip(client))]
 async fn initial_update_topology(
     initial_urls: Vec<Url>,
     database: String,
-    application_id: Uuid,
+    application_id: Option<Uuid>,
+    client: HttpClient,
+    topology_cache_location: Option<PathBuf>,
 ) -> Result<DatabaseTopology, Vec<(Url, RequestExecutorError)>> {
     // Note: Java client implementation validates URL strings here.
     // This rust library does not because the strings are validated by the DocumentStoreBuilder
@@ -258,9 +753,8 @@ async fn initial_update_topology(
         let server_node = ServerNode::new(url.clone(), database.clone());
         let update_parameters = UpdateTopologyParameters {
             server_node: server_node.clone(),
-            timeout_in_ms: i32::MAX, //TODO: Is this necessary? I believe it has something to do with a tcp timeout bug, but maybe only in java or C#
-            force_update: false,
             application_id,
+            client: client.clone(),
         };
 
         let x = update_topology_async(update_parameters).await;
@@ -280,120 +774,357 @@ async fn initial_update_topology(
     // If this point is reached, none of the provided URLs succeeded in providing a topology
     // for one reason or another.
 
-    // Create a set of [`ServerNode`]s from the initial urls. Hope these
-    // servers are actually online and listening.
-    let nodes = initial_urls
-        .iter()
-        .map(|url| {
-            let mut server_node = ServerNode::new(url.clone(), database.clone());
-
-            server_node.cluster_tag = "!".to_string();
-            server_node
-        })
-        .collect::<HashSet<ServerNode>>();
-
-    // Create a new topology from manufactured one above.
-    let topology = DatabaseTopology {
-        nodes,
-        ..Default::default()
-    };
+    // Fall back to the topology cached by an earlier run. Its nodes may include servers that
+    // are up even though none of the initial urls are.
+    if let Some(cache_location) = topology_cache_location.as_deref() {
+        if let Some(topology) =
+            topology_cache::try_load_database_topology(cache_location, &initial_urls, &database)
+                .await
+        {
+            tracing::warn!("No initial url responded. Using the cached database topology.");
+            return Ok(topology);
+        }
 
-    // Ensure the user did not somehow pass an empty list of URLs.
-    if !initial_urls.is_empty() {
-        // No timer initialized here like JVM client. Actor runner handles the timer.
-        return Ok(topology);
+        if let Some(cluster_topology) =
+            topology_cache::try_load_cluster_topology(cache_location, &initial_urls).await
+        {
+            tracing::warn!("No initial url responded. Using the cached cluster topology.");
+            let nodes = cluster_topology
+                .all_nodes
+                .iter()
+                .map(|(tag, url)| {
+                    let mut server_node = ServerNode::new(url.clone(), database.clone());
+                    server_node.cluster_tag = tag.clone();
+                    server_node
+                })
+                .collect::<HashSet<ServerNode>>();
+            if !nodes.is_empty() {
+                return Ok(DatabaseTopology {
+                    nodes,
+                    ..Default::default()
+                });
+            }
+        }
     }
 
-    // Return the errors to the caller to deal with
-    let server_errors = server_errors
-        .into_iter()
-        .map(|e| (e.0, e.1))
-        .collect::<Vec<_>>();
+    // NOTE: The JVM client manufactures a topology from the initial urls at this point and
+    // hopes they come online later. This library returns the errors to the caller instead, so
+    // a store that can't reach its cluster finds out when it asks for an executor rather than
+    // on its first request.
     Err(server_errors)
 }
 
+/// Downloads the database topology from the node in `parameters`.
+#[instrument(level = "debug", skip(parameters), fields(url = %parameters.server_node.url))]
 async fn update_topology_async(
     parameters: UpdateTopologyParameters,
 ) -> Result<DatabaseTopology, RequestExecutorError> {
-    todo!();
+    let command = GetDatabaseTopologyCommand {
+        application_identifier: parameters.application_id,
+    };
+    let response = send_raven_command_request_to_server(
+        &parameters.client,
+        command.create_request(&parameters.server_node)?,
+        0,
+        0,
+    )
+    .await?;
+
+    Ok(command.parse_response(response).await?)
+}
+
+/// Sends the command to each of the nodes in turn until one of them answers.
+async fn execute_with_failover(
+    parameters: ExecuteParameters,
+) -> Result<reqwest::Response, RequestExecutorError> {
+    let ExecuteParameters {
+        command,
+        nodes,
+        timeout,
+        compression,
+        client,
+        http_cache,
+        topology_etag,
+        client_configuration_etag,
+        sender_internal,
+    } = parameters;
+    let mut errors = Vec::new();
+
+    for node in nodes {
+        let mut request = command.create_request(&node)?;
+        if let Some(timeout) = timeout {
+            // Times out as a `RavenDbError::Timeout`, which fails over like any other node failure
+            *request.timeout_mut() = Some(timeout);
+        }
+        if let Some(algorithm) = compression {
+            compression::compress_request(&mut request, algorithm)?;
+        }
+
+        let started = Instant::now();
+        let result = match send_with_http_cache(
+            &client,
+            &http_cache,
+            request,
+            topology_etag,
+            client_configuration_etag,
+        )
+        .await
+        {
+            Ok(response) => check_response(response, command.handles_error_responses()).await,
+            Err(e) => Err(e),
+        };
+
+        let response = match result {
+            Ok(response) => response,
+            Err(e) if e.is_node_failure() => {
+                tracing::warn!(
+                    "Node `{}` failed to respond. Trying the next node. Caused by: {}",
+                    node.url,
+                    e
+                );
+                let _ = sender_internal
+                    .send(RequestExecutorMessage::NodeFailed { node: node.clone() })
+                    .await;
+                errors.push((node.url, e));
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        // Feed the response time back to the node selector for `FastestNode`
+        let response_time_ms = u32::try_from(started.elapsed().as_millis()).unwrap_or(u32::MAX);
+        let _ = sender_internal
+            .send(RequestExecutorMessage::RecordResponseTime {
+                node,
+                response_time_ms,
+            })
+            .await;
+
+        if header_is_true(&response, "Refresh-Topology") {
+            if let Err(e) = sender_internal
+                .send(RequestExecutorMessage::UpdateTopology)
+                .await
+            {
+                tracing::error!(
+                    "Could not send internal message to request topology update. Caused by: {}",
+                    e
+                );
+            }
+        }
+        if header_is_true(&response, "Refresh-Client-Configuration") {
+            if let Err(e) = sender_internal
+                .send(RequestExecutorMessage::UpdateClientConfiguration)
+                .await
+            {
+                tracing::error!(
+                    "Could not send internal message to request client configuration update. Caused by: {}",
+                    e
+                );
+            }
+        }
+
+        return Ok(response);
+    }
+
+    Err(RavenDbError::AllTopologyNodesDown { errors }.into())
 }
 
-struct TopologyUpdateResult {
-    topology: DatabaseTopology,
+struct ExecuteParameters {
+    command: Arc<dyn super::RequestFactory>,
+    nodes: Vec<ServerNode>,
+    timeout: Option<Duration>,
+    /// Set when request bodies and responses should be compressed.
+    compression: Option<HttpCompressionAlgorithm>,
+    client: HttpClient,
+    http_cache: Arc<Mutex<HttpCache>>,
+    topology_etag: u64,
+    client_configuration_etag: i64,
+    sender_internal: mpsc::Sender<RequestExecutorMessage>,
 }
 
 struct UpdateTopologyParameters {
     server_node: ServerNode,
-    timeout_in_ms: i32,
-    force_update: bool,
-    application_id: Uuid,
+    /// Sent to identify the client, unless the conventions say otherwise.
+    application_id: Option<Uuid>,
+    client: HttpClient,
+}
+
+/// Returns `true` if a background task is stored and has not finished yet.
+fn is_running(handle: &Option<JoinHandle<()>>) -> bool {
+    handle
+        .as_ref()
+        .map(|handle| !handle.is_finished())
+        .unwrap_or(false)
+}
+
+/// Returns `true` if the response carries the given header with the value `true`.
+fn header_is_true(response: &reqwest::Response, header: &str) -> bool {
+    response
+        .headers()
+        .get(header)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
 }
 
-#[instrument(level = "debug", skip(client_identity))]
+#[instrument(level = "debug", skip(client))]
 async fn send_raven_command_request_to_server(
-    client_identity: Option<Identity>,
-    dns_overrides: DnsOverrides,
-    proxy_address: Option<String>,
-    raven_command: RavenCommand,
+    client: &HttpClient,
+    mut request: reqwest::Request,
     topology_etag: u64,
-) -> anyhow::Result<reqwest::Response> {
-    let mut client = reqwest::Client::builder();
+    client_configuration_etag: i64,
+) -> Result<reqwest::Response, RavenDbError> {
+    request
+        .headers_mut()
+        .append("Topology-Etag", HeaderValue::from(topology_etag));
+    let headerval = HeaderValue::from(client_configuration_etag);
+    request
+        .headers_mut()
+        .append("Client-Configuration-Etag", headerval);
+    tracing::trace!("Request Headers: {:#?}", &request.headers());
+    let response = client.execute(request).await?;
 
-    if let Some(identity) = client_identity.clone() {
-        client = client.identity(identity).use_rustls_tls();
-    }
+    compression::decompress_response(response).await
+}
 
-    // Convert Option<HashMap<String, IpAddr>> into HashMap<String,SocketAddr>
-    let overrides = dns_overrides
-        .into_iter()
-        .map(|(k, v)| (k, SocketAddr::new(v, 0)))
-        .collect::<HashMap<String, SocketAddr>>();
+/// Sends the request, revalidating GET requests against the HTTP cache.
+///
+/// A cached response is sent along with its etag in `If-None-Match`. If the server answers
+/// `304 Not Modified`, the cached response is returned in place of the empty one. Successful
+/// responses carrying an etag are stored for next time. While aggressive caching is on, recent
+/// cached responses are returned without sending the request at all.
+async fn send_with_http_cache(
+    client: &HttpClient,
+    http_cache: &Mutex<HttpCache>,
+    mut request: reqwest::Request,
+    topology_etag: u64,
+    client_configuration_etag: i64,
+) -> Result<reqwest::Response, RavenDbError> {
+    if request.method() != Method::GET || !lock_http_cache(http_cache).is_enabled() {
+        return send_raven_command_request_to_server(
+            client,
+            request,
+            topology_etag,
+            client_configuration_etag,
+        )
+        .await;
+    }
 
-    for (domain, address) in overrides {
-        tracing::trace!(
-            "Adding `{}->{}` to dns overrides for this request.",
-            domain,
-            address
-        );
-        client = client.resolve(domain.as_str(), address);
+    let url = request.url().to_string();
+    let generation = {
+        let mut http_cache = lock_http_cache(http_cache);
+        if let Some(cached) = http_cache.aggressive_hit(&url) {
+            return Ok(cached.to_response(request.url().clone()));
+        }
+        if let Some(etag) = http_cache.etag_for(&url) {
+            request.headers_mut().insert(IF_NONE_MATCH, etag);
+        }
+        http_cache.generation()
+    };
+    // Keep an unconditional copy, in case the cached response is evicted while revalidating
+    let mut retry = request.try_clone();
+    if let Some(retry) = retry.as_mut() {
+        retry.headers_mut().remove(IF_NONE_MATCH);
     }
 
-    if let Some(proxy) = proxy_address {
-        tracing::trace!("Proxy set to `{}`", &proxy);
-        client = client.proxy(reqwest::Proxy::http(proxy)?);
+    let response = send_raven_command_request_to_server(
+        client,
+        request,
+        topology_etag,
+        client_configuration_etag,
+    )
+    .await?;
+
+    let response = if response.status() == StatusCode::NOT_MODIFIED {
+        if let Some(cached) = lock_http_cache(http_cache).hit(&url, generation) {
+            return Ok(cached.to_response(response.url().clone()));
+        }
+        match retry {
+            Some(retry) => {
+                send_raven_command_request_to_server(
+                    client,
+                    retry,
+                    topology_etag,
+                    client_configuration_etag,
+                )
+                .await?
+            }
+            None => response,
+        }
     } else {
-        tracing::trace!("No proxy defined. Using system settings.");
-    }
+        response
+    };
+    lock_http_cache(http_cache).miss();
 
-    let client = client.build()?;
+    let etag = response.headers().get(ETAG).cloned();
+    let Some(etag) = etag.filter(|_| response.status() == StatusCode::OK) else {
+        return Ok(response);
+    };
 
-    let mut request = raven_command.get_http_request()?;
-    let headerval = HeaderValue::from_str(topology_etag.to_string().as_str())?;
-    request.headers_mut().append("Topology-Etag", headerval);
-    tracing::trace!("Request Headers: {:#?}", &request.headers());
-    let response = client.execute(request).await?;
+    let response_url = response.url().clone();
+    let cached = CachedResponse {
+        etag,
+        status: response.status(),
+        headers: response.headers().clone(),
+        body: response.bytes().await?,
+    };
+    let response = cached.to_response(response_url);
+    lock_http_cache(http_cache).insert(url, cached, generation);
 
     Ok(response)
 }
 
+/// Turns an unsuccessful response into an error, unless the command handles those itself.
+/// Responses from nodes that are down are always errors, so the request fails over.
+async fn check_response(
+    response: reqwest::Response,
+    handles_error_responses: bool,
+) -> Result<reqwest::Response, RavenDbError> {
+    let status = response.status();
+    if status.is_success() || (handles_error_responses && !is_node_failure_status(status)) {
+        return Ok(response);
+    }
+    Err(RavenDbError::from_response(response).await)
+}
+
+/// Returns `period` plus a random delay of up to `jitter`.
+fn with_jitter(period: Duration, jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        return period;
+    }
+    period + rand::thread_rng().gen_range(Duration::ZERO..=jitter)
+}
+
 #[instrument(level = "debug", name = "Running Document Store Actor", skip(actor))]
 pub async fn run_request_executor_actor(mut actor: RequestExecutorActor) {
-    // Run a 5 minute timer to send topology update requests to the actor
-    let mut topology_update_timer = tokio::time::interval(tokio::time::Duration::from_secs(60 * 5));
-    // Run a 1 minute timer to request database topology updates
-    let mut topology_update_timer_1min =
-        tokio::time::interval(tokio::time::Duration::from_secs(60));
+    let cluster_interval = actor.conventions.cluster_topology_refresh_interval();
+    let database_interval = actor.conventions.database_topology_refresh_interval();
+    let jitter = actor.conventions.topology_refresh_jitter();
+
+    // Both timers fire for the first time after a random delay, then every interval plus a new
+    // random delay, so clients started at the same time drift apart instead of refreshing in step.
+    let cluster_topology_timer = tokio::time::sleep(with_jitter(Duration::ZERO, jitter));
+    let database_topology_timer = tokio::time::sleep(with_jitter(Duration::ZERO, jitter));
+    tokio::pin!(cluster_topology_timer, database_topology_timer);
+
     loop {
         tokio::select! {
-            // 5 minute timer
-            _ = topology_update_timer.tick() => {
-                tracing::debug!("Updating topology via timer.");
-                let _= actor.sender_internal.send(RequestExecutorMessage::UpdateTopology).await;
+            // The timers hand the message straight to the actor. Sending it through
+            // `sender_internal` would deadlock once that channel is full, as this loop is its
+            // only reader.
+            () = &mut cluster_topology_timer, if !actor.disable_topology_updates => {
+                cluster_topology_timer
+                    .as_mut()
+                    .reset(Instant::now() + with_jitter(cluster_interval, jitter));
+                tracing::debug!("Updating cluster topology via timer.");
+                actor.handle_message(RequestExecutorMessage::UpdateClusterTopology).await;
             }
-            // 1 minute timer
-            _ = topology_update_timer_1min.tick() => {
-                tracing::debug!("Updating topology via 1 minute timer.");
-                let _ = actor.sender_internal.send(RequestExecutorMessage::UpdateTopology).await;
+            () = &mut database_topology_timer, if !actor.disable_topology_updates => {
+                database_topology_timer
+                    .as_mut()
+                    .reset(Instant::now() + with_jitter(database_interval, jitter));
+                tracing::debug!("Updating database topology via timer.");
+                actor.handle_message(RequestExecutorMessage::UpdateTopology).await;
             }
             // Messages from the handle
             external_message = actor.receiver.recv() => {
@@ -410,3 +1141,378 @@ pub async fn run_request_executor_actor(mut actor: RequestExecutorActor) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt;
+    use reqwest::Url;
+    use tokio::{
+        net::TcpListener,
+        sync::{mpsc, oneshot},
+    };
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        database_topology::GetDatabaseTopologyResult, document_conventions::DocumentConventions,
+        AggressiveCacheGuard, LoadBalanceBehavior, ReadBalanceBehavior, SessionInfo,
+    };
+
+    use super::{
+        initial_update_topology, topology_cache, with_jitter, RequestExecutorActor,
+        RequestExecutorMessage,
+    };
+
+    fn actor_for(server: &MockServer) -> RequestExecutorActor {
+        let (_, receiver) = mpsc::channel(1);
+        RequestExecutorActor::new(
+            receiver,
+            "db".to_string(),
+            vec![Url::parse(&server.uri()).unwrap()],
+            Default::default(),
+            DocumentConventions::default(),
+        )
+        .unwrap()
+    }
+
+    fn topology_body(server: &MockServer, etag: i64) -> serde_json::Value {
+        serde_json::json!({
+            "Nodes": [
+                { "Url": server.uri(), "ClusterTag": "A", "Database": "db", "ServerRole": "Member" }
+            ],
+            "Etag": etag
+        })
+    }
+
+    #[tokio::test]
+    async fn update_topology_replaces_topology_with_server_response() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .and(query_param("name", "db"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(topology_body(&server, 5)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut actor = actor_for(&server);
+
+        // Act
+        actor
+            .handle_message(RequestExecutorMessage::UpdateTopology)
+            .await;
+        let msg = actor.receiver_internal.recv().await.unwrap();
+        actor.handle_message(msg).await;
+
+        // Assert
+        let topology = actor.get_topology().unwrap();
+        assert_eq!(topology.etag, 5);
+        assert_eq!(
+            topology.nodes.iter().next().unwrap().cluster_tag,
+            "A".to_string()
+        );
+        assert!(actor.node_selector.is_some());
+    }
+
+    #[tokio::test]
+    async fn initial_topology_routes_sessions_by_their_context() {
+        // Arrange
+        let a = MockServer::start().await;
+        let b = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Nodes": [
+                    { "Url": a.uri(), "ClusterTag": "A", "Database": "db", "ServerRole": "Member" },
+                    { "Url": b.uri(), "ClusterTag": "B", "Database": "db", "ServerRole": "Member" }
+                ],
+                "Etag": 1
+            })))
+            .mount(&a)
+            .await;
+        let (_, receiver) = mpsc::channel(1);
+        let mut actor = RequestExecutorActor::new(
+            receiver,
+            "db".to_string(),
+            vec![Url::parse(&a.uri()).unwrap()],
+            Default::default(),
+            DocumentConventions::default()
+                .set_load_balance_behavior(LoadBalanceBehavior::UseSessionContext),
+        )
+        .unwrap();
+        let (tx, rx) = oneshot::channel();
+        actor
+            .handle_message(RequestExecutorMessage::InitialUpdateTopology {
+                initial_urls: vec![Url::parse(&a.uri()).unwrap()],
+                respond_to: tx,
+            })
+            .await;
+        rx.await.unwrap().unwrap();
+        let mut first = SessionInfo::new();
+        let mut second = SessionInfo::new();
+        first.set_load_balancer_context("tenants/1");
+        second.set_load_balancer_context("tenants/1");
+
+        // Act
+        let first_node = actor.choose_node_for_request(false, Some(&first));
+        let second_node = actor.choose_node_for_request(true, Some(&second));
+
+        // Assert
+        assert!(first_node.is_some());
+        assert_eq!(first_node, second_node);
+    }
+
+    #[tokio::test]
+    async fn dropping_the_last_aggressive_cache_guard_closes_the_changes_connection() {
+        // Arrange
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let (watching_tx, watching_rx) = oneshot::channel();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for _ in 0..2 {
+                socket.next().await.unwrap().unwrap();
+            }
+            let _ = watching_tx.send(());
+            // Ends once the client closes the connection
+            while let Some(Ok(_)) = socket.next().await {}
+        });
+        let (_, receiver) = mpsc::channel(1);
+        let mut actor = RequestExecutorActor::new(
+            receiver,
+            "db".to_string(),
+            vec![url],
+            Default::default(),
+            DocumentConventions::default(),
+        )
+        .unwrap();
+        let guard = AggressiveCacheGuard::new(actor.http_cache(), Duration::from_secs(60));
+        actor
+            .handle_message(RequestExecutorMessage::WatchChanges)
+            .await;
+        watching_rx.await.unwrap();
+
+        // Act
+        drop(guard);
+
+        // Assert
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("the changes connection should be closed")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn update_topology_does_not_start_a_second_refresh_while_one_is_running() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(topology_body(&server, 5)))
+            // Verified when the server is dropped
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut actor = actor_for(&server);
+
+        // Act
+        actor
+            .handle_message(RequestExecutorMessage::UpdateTopology)
+            .await;
+        actor
+            .handle_message(RequestExecutorMessage::UpdateTopology)
+            .await;
+        let msg = actor.receiver_internal.recv().await.unwrap();
+
+        // Assert
+        assert!(matches!(
+            msg,
+            RequestExecutorMessage::TopologyUpdated { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn topology_updated_ignores_older_topology() {
+        // Arrange
+        let server = MockServer::start().await;
+        let mut actor = actor_for(&server);
+        let newer =
+            serde_json::from_value::<GetDatabaseTopologyResult>(topology_body(&server, 7)).unwrap();
+        let older =
+            serde_json::from_value::<GetDatabaseTopologyResult>(topology_body(&server, 3)).unwrap();
+
+        // Act
+        actor
+            .handle_message(RequestExecutorMessage::TopologyUpdated {
+                topology: newer.into(),
+            })
+            .await;
+        actor
+            .handle_message(RequestExecutorMessage::TopologyUpdated {
+                topology: older.into(),
+            })
+            .await;
+
+        // Assert
+        assert_eq!(actor.get_topology().unwrap().etag, 7);
+    }
+
+    #[tokio::test]
+    async fn update_client_configuration_stores_server_configuration() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/databases/db/configuration/client"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Etag": 2,
+                "Configuration": { "Disabled": false, "ReadBalanceBehavior": "RoundRobin" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut actor = actor_for(&server);
+
+        // Act
+        actor
+            .handle_message(RequestExecutorMessage::UpdateClientConfiguration)
+            .await;
+        let msg = actor.receiver_internal.recv().await.unwrap();
+        actor.handle_message(msg).await;
+
+        // Assert
+        assert_eq!(actor.client_configuration_etag, 2);
+        assert_eq!(
+            actor
+                .client_configuration
+                .as_ref()
+                .and_then(|c| c.read_balance_behavior),
+            Some(ReadBalanceBehavior::RoundRobin)
+        );
+    }
+
+    #[tokio::test]
+    async fn initial_update_topology_falls_back_to_cached_topology() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let cache_location = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let initial_urls = vec![Url::parse(&server.uri()).unwrap()];
+        let cached = serde_json::from_value::<GetDatabaseTopologyResult>(serde_json::json!({
+            "Nodes": [
+                { "Url": "http://raven2:8080", "ClusterTag": "B", "Database": "db" }
+            ],
+            "Etag": 9
+        }))
+        .unwrap();
+        topology_cache::try_save_database_topology(
+            cache_location.clone(),
+            initial_urls.clone(),
+            "db".to_string(),
+            cached.into(),
+        )
+        .await;
+
+        // Act
+        let result = initial_update_topology(
+            initial_urls,
+            "db".to_string(),
+            Some(uuid::Uuid::new_v4()),
+            reqwest::Client::new().into(),
+            Some(cache_location.clone()),
+        )
+        .await;
+        let _ = std::fs::remove_dir_all(&cache_location);
+
+        // Assert
+        let topology = result.unwrap();
+        assert_eq!(topology.etag, 9);
+        assert_eq!(topology.nodes.iter().next().unwrap().cluster_tag, "B");
+    }
+
+    #[tokio::test]
+    async fn single_node_actor_never_updates_topology() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(topology_body(&server, 5)))
+            // Verified when the server is dropped
+            .expect(0)
+            .mount(&server)
+            .await;
+        let mut actor = actor_for(&server).with_single_node(true);
+
+        // Act
+        actor
+            .handle_message(RequestExecutorMessage::UpdateTopology)
+            .await;
+        actor
+            .handle_message(RequestExecutorMessage::UpdateClientConfiguration)
+            .await;
+
+        // Assert
+        assert!(actor.topology_updater.is_none());
+        assert!(actor.client_configuration_updater.is_none());
+        let node = actor.choose_node_for_request(true, None).unwrap();
+        assert_eq!(node.url, Url::parse(&server.uri()).unwrap());
+        assert_eq!(node.cluster_tag, "!");
+    }
+
+    #[test]
+    fn with_jitter_stays_within_bounds() {
+        let period = Duration::from_secs(60);
+        let jitter = Duration::from_secs(10);
+
+        for _ in 0..100 {
+            let result = with_jitter(period, jitter);
+            assert!(result >= period && result <= period + jitter);
+        }
+        assert_eq!(with_jitter(period, Duration::ZERO), period);
+    }
+
+    #[tokio::test]
+    async fn update_cluster_topology_stores_server_topology() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/cluster/topology"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Topology": {
+                    "TopologyId": "id",
+                    "AllNodes": { "A": server.uri() },
+                    "Members": { "A": server.uri() },
+                    "Promotables": {},
+                    "Watchers": {},
+                    "Etag": 4
+                },
+                "Etag": 4,
+                "Leader": "A",
+                "NodeTag": "A"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let mut actor = actor_for(&server);
+
+        // Act
+        actor
+            .handle_message(RequestExecutorMessage::UpdateClusterTopology)
+            .await;
+        let msg = actor.receiver_internal.recv().await.unwrap();
+        actor.handle_message(msg).await;
+
+        // Assert
+        let topology = actor.cluster_topology.unwrap();
+        assert_eq!(topology.etag, 4);
+        assert!(topology.members.contains_key("A"));
+    }
+}
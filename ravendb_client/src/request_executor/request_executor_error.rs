@@ -1,7 +1,13 @@
-use crate::error_chain_fmt;
+use reqwest::Url;
+
+use crate::{error_chain_fmt, ravendb_error::RavenDbError};
 
 #[derive(thiserror::Error)]
 pub enum RequestExecutorError {
+    #[error("None of the initial urls returned a topology: {}", format_url_errors(.0))]
+    InitialTopologyUpdateFailed(Vec<(Url, RequestExecutorError)>),
+    #[error(transparent)]
+    RavenDbError(#[from] RavenDbError),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -10,3 +16,11 @@ impl std::fmt::Debug for RequestExecutorError {
         error_chain_fmt(self, f)
     }
 }
+
+fn format_url_errors(errors: &[(Url, RequestExecutorError)]) -> String {
+    errors
+        .iter()
+        .map(|(url, e)| format!("`{}`: {}", url, e))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -1,95 +1,223 @@
-use reqwest::{Identity, Url};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::Url;
 use tokio::sync::{mpsc, oneshot};
 use tracing::instrument;
 
-use crate::{document_conventions::DocumentConventions, raven_command::RavenCommand, DnsOverrides};
+use crate::{document_conventions::DocumentConventions, raven_command::RavenCommand, SessionInfo};
 
 use super::{
-    request_executor_actor::run_request_executor_actor, RequestExecutorActor, RequestExecutorError,
-    RequestExecutorMessage,
+    http_cache::HttpCache, http_client::HttpClientSettings,
+    request_executor_actor::run_request_executor_actor, AggressiveCacheGuard, HttpCacheStats,
+    RequestExecutorActor, RequestExecutorError, RequestExecutorMessage, RequestFactory,
 };
 
 #[derive(Clone, Debug)]
 pub struct RequestExecutor {
+    /// Shared with the actor, so aggressive caching can be turned on and off without a message.
+    http_cache: Arc<Mutex<HttpCache>>,
     sender: mpsc::Sender<RequestExecutorMessage>,
 }
 
 impl RequestExecutor {
-    pub(crate) fn new(
+    /// Spawns a new executor actor and waits for it to download its first topology.
+    ///
+    /// Returns [`RequestExecutorError::InitialTopologyUpdateFailed`] with the error of every
+    /// initial url if none of them returned a topology and none was cached.
+    pub(crate) async fn new(
         initial_urls: Vec<Url>,
         database_name: String,
-        dns_overrides: DnsOverrides,
-        proxy_address: Option<String>,
-        identity: Option<Identity>,
+        http_client_settings: HttpClientSettings,
         conventions: DocumentConventions,
-    ) -> Self {
+    ) -> Result<Self, RequestExecutorError> {
         let (sender, receiver) = mpsc::channel(8);
         let actor = RequestExecutorActor::new(
             receiver,
             database_name,
-            identity,
             initial_urls.clone(),
-            dns_overrides,
-            proxy_address,
+            http_client_settings,
             conventions,
-        );
+        )?;
+        let http_cache = actor.http_cache();
 
         tokio::spawn(run_request_executor_actor(actor));
 
-        // Tell the actor to do it's first topology update
-        let _ =
-            sender.blocking_send(RequestExecutorMessage::InitialUpdateTopology { initial_urls });
+        // Tell the actor to do it's first topology update and wait for it to finish
+        let (respond_to, receiver) = oneshot::channel();
+        let _ = sender
+            .send(RequestExecutorMessage::InitialUpdateTopology {
+                initial_urls,
+                respond_to,
+            })
+            .await;
 
-        Self { sender }
+        match receiver.await {
+            Ok(result) => result.map(|_| Self { http_cache, sender }),
+            Err(e) => Err(RequestExecutorError::UnexpectedError(anyhow::anyhow!(
+                "Could not receive initial topology from request executor actor. Actor probably died. Caused by: {}",
+                e
+            ))),
+        }
     }
 
+    /// Creates a [`RequestExecutor`] that only ever talks to `url` and never updates its
+    /// topology, but still follows the client configuration pushed by the server.
     pub(crate) fn new_for_single_node_with_configuration_updates(
         url: Url,
         database_name: String,
-        dns_overrides: DnsOverrides,
-        proxy_address: Option<String>,
-        identity: Option<Identity>,
+        http_client_settings: HttpClientSettings,
         conventions: DocumentConventions,
-    ) -> Self {
-        //TODO: Finish this method
-        RequestExecutor::new_for_single_node_without_configuration_updates(
+    ) -> Result<Self, RequestExecutorError> {
+        RequestExecutor::new_for_single_node(
             url,
             database_name,
-            dns_overrides,
-            proxy_address,
-            identity,
+            http_client_settings,
             conventions,
+            false,
         )
     }
 
+    /// Creates a [`RequestExecutor`] that only ever talks to `url`, and neither updates its
+    /// topology nor its client configuration.
     pub(crate) fn new_for_single_node_without_configuration_updates(
         url: Url,
         database_name: String,
-        dns_overrides: DnsOverrides,
-        proxy_address: Option<String>,
-        identity: Option<Identity>,
+        http_client_settings: HttpClientSettings,
         conventions: DocumentConventions,
-    ) -> Self {
-        //TODO: Finish this method
-        RequestExecutor::new(
-            vec![url],
+    ) -> Result<Self, RequestExecutorError> {
+        RequestExecutor::new_for_single_node(
+            url,
             database_name,
-            dns_overrides,
-            proxy_address,
-            identity,
+            http_client_settings,
             conventions,
+            true,
         )
     }
 
-    #[instrument(level = "DEBUG", skip(self))]
-    pub(crate) async fn execute_request(
+    fn new_for_single_node(
+        url: Url,
+        database_name: String,
+        http_client_settings: HttpClientSettings,
+        conventions: DocumentConventions,
+        disable_client_configuration_updates: bool,
+    ) -> Result<Self, RequestExecutorError> {
+        let (sender, receiver) = mpsc::channel(8);
+        let actor = RequestExecutorActor::new(
+            receiver,
+            database_name,
+            vec![url],
+            http_client_settings,
+            conventions,
+        )?
+        .with_single_node(disable_client_configuration_updates);
+        let http_cache = actor.http_cache();
+
+        // No initial topology update is needed, the single node is the topology.
+        tokio::spawn(run_request_executor_actor(actor));
+
+        Ok(Self { http_cache, sender })
+    }
+
+    /// Executes the command against a node chosen by the conventions' load balancing rules and
+    /// returns its parsed result.
+    ///
+    /// Supplying the [`SessionInfo`] of the calling session lets the executor keep all of that
+    /// session's requests on the same node.
+    #[instrument(level = "DEBUG", skip(self, command))]
+    pub async fn execute<C: RavenCommand>(
+        &self,
+        command: C,
+        session_info: Option<SessionInfo>,
+    ) -> Result<C::Result, RequestExecutorError> {
+        let command = Arc::new(command);
+        let response = self.execute_request(command.clone(), session_info).await?;
+
+        // Parsing happens on the caller's task so a slow body doesn't hold up the actor
+        Ok(command.parse_response(response).await?)
+    }
+
+    /// Serves GET requests from the HTTP cache without asking the server, for as long as the
+    /// returned guard is alive.
+    ///
+    /// Cached responses younger than `duration` are returned as is. The executor watches the
+    /// database's changes meanwhile, and revalidates every cached response once a document or
+    /// index changed. Until that watch is established, responses are revalidated as usual.
+    pub async fn aggressively_cache_for(&self, duration: Duration) -> AggressiveCacheGuard {
+        let guard = AggressiveCacheGuard::new(self.http_cache.clone(), duration);
+        let _ = self.sender.send(RequestExecutorMessage::WatchChanges).await;
+        guard
+    }
+
+    /// Returns the store's conventions with the values of the server's client configuration,
+    /// such as the maximum number of requests per session, applied on top.
+    pub(crate) async fn conventions(&self) -> Result<DocumentConventions, RequestExecutorError> {
+        let (respond_to, receiver) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(RequestExecutorMessage::GetConventions { respond_to })
+            .await;
+
+        receiver.await.map_err(|e| {
+            RequestExecutorError::UnexpectedError(anyhow::anyhow!(
+                "Could not receive conventions from request executor actor. Actor probably died. Caused by: {}",
+                e
+            ))
+        })
+    }
+
+    /// Returns the current size and hit rate of the executor's HTTP cache.
+    pub async fn http_cache_stats(&self) -> Result<HttpCacheStats, RequestExecutorError> {
+        let (respond_to, receiver) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(RequestExecutorMessage::GetHttpCacheStats { respond_to })
+            .await;
+
+        receiver.await.map_err(|e| {
+            RequestExecutorError::UnexpectedError(anyhow::anyhow!(
+                "Could not receive HTTP cache stats from request executor actor. Actor probably died. Caused by: {}",
+                e
+            ))
+        })
+    }
+
+    /// Makes every request sent from now on present `client_certificate_pem`, or no certificate
+    /// at all. Requests already sent finish with the previous certificate.
+    pub(crate) async fn replace_client_certificate(
         &self,
-        command: RavenCommand,
+        client_certificate_pem: Option<Vec<u8>>,
+    ) -> Result<(), RequestExecutorError> {
+        let (respond_to, receiver) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(RequestExecutorMessage::ReplaceClientCertificate {
+                client_certificate_pem,
+                respond_to,
+            })
+            .await;
+
+        receiver.await.map_err(|e| {
+            RequestExecutorError::UnexpectedError(anyhow::anyhow!(
+                "Could not receive result from request executor actor. Actor probably died. Caused by: {}",
+                e
+            ))
+        })?
+    }
+
+    /// Sends the command to the actor and returns the server's response without parsing it.
+    async fn execute_request(
+        &self,
+        command: Arc<dyn RequestFactory>,
+        session_info: Option<SessionInfo>,
     ) -> Result<reqwest::Response, RequestExecutorError> {
         let (respond_to, receiver) = oneshot::channel();
         let executemsg = RequestExecutorMessage::ExecuteRavenCommand {
             respond_to,
             command,
+            session_info,
         };
         let _ = self.sender.send(executemsg).await;
 
@@ -108,3 +236,529 @@ impl RequestExecutor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, SystemTime},
+    };
+
+    use reqwest::Url;
+    use rustls::{
+        server::{ClientCertVerified, ClientCertVerifier},
+        Certificate, DistinguishedName, PrivateKey, ServerConfig,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_rustls::TlsAcceptor;
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        document_conventions::{DocumentConventions, HttpCompressionAlgorithm},
+        raven_command::{RavenCommand, RawCommand},
+        ravendb_error::RavenDbError,
+        request_executor::{HttpClientSettings, TrustStore},
+        server_node::ServerNode,
+    };
+
+    use super::{RequestExecutor, RequestExecutorError};
+
+    /// A command this library doesn't know about, to prove any [`RavenCommand`] can be executed.
+    struct GetBuildNumberCommand;
+
+    impl RavenCommand for GetBuildNumberCommand {
+        type Result = i64;
+
+        fn create_request(&self, node: &ServerNode) -> anyhow::Result<reqwest::Request> {
+            Ok(reqwest::Request::new(
+                reqwest::Method::GET,
+                node.url.join("build/version")?,
+            ))
+        }
+
+        fn is_read_request(&self) -> bool {
+            true
+        }
+
+        async fn parse_response(&self, response: reqwest::Response) -> anyhow::Result<i64> {
+            let body = response.json::<serde_json::Value>().await?;
+            body["BuildVersion"]
+                .as_i64()
+                .ok_or_else(|| anyhow::anyhow!("Missing build version"))
+        }
+    }
+
+    /// Accepts any client certificate, recording the ones presented.
+    struct RecordingClientCertVerifier {
+        presented: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl ClientCertVerifier for RecordingClientCertVerifier {
+        fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+            &[]
+        }
+
+        fn verify_client_cert(
+            &self,
+            end_entity: &Certificate,
+            _intermediates: &[Certificate],
+            _now: SystemTime,
+        ) -> Result<ClientCertVerified, rustls::Error> {
+            self.presented.lock().unwrap().push(end_entity.0.clone());
+            Ok(ClientCertVerified::assertion())
+        }
+    }
+
+    /// Starts an HTTPS server on localhost that requires a client certificate and answers every
+    /// request with a build version. Returns its url, the PEM of its certificate to trust, and
+    /// the client certificates presented to it.
+    async fn start_tls_server() -> (Url, String, Arc<Mutex<Vec<Vec<u8>>>>) {
+        let certificate =
+            rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let presented = Arc::new(Mutex::new(Vec::new()));
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(Arc::new(RecordingClientCertVerifier {
+                presented: presented.clone(),
+            }))
+            .with_single_cert(
+                vec![Certificate(certificate.serialize_der().unwrap())],
+                PrivateKey(certificate.serialize_private_key_der()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!(
+            "https://localhost:{}",
+            listener.local_addr().unwrap().port()
+        ))
+        .unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(mut stream) = acceptor.accept(stream).await else {
+                    continue;
+                };
+                let mut request = Vec::new();
+                let mut buffer = [0; 1024];
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buffer).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(read) => request.extend_from_slice(&buffer[..read]),
+                    }
+                }
+                let body = r#"{"BuildVersion":54}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+                let _ = stream.shutdown().await;
+            }
+        });
+
+        (url, certificate.serialize_pem().unwrap(), presented)
+    }
+
+    async fn new_executor(server: &MockServer) -> Result<RequestExecutor, RequestExecutorError> {
+        new_executor_with_conventions(server, DocumentConventions::default()).await
+    }
+
+    async fn new_executor_with_conventions(
+        server: &MockServer,
+        conventions: DocumentConventions,
+    ) -> Result<RequestExecutor, RequestExecutorError> {
+        RequestExecutor::new(
+            vec![Url::parse(&server.uri()).unwrap()],
+            "db".to_string(),
+            Default::default(),
+            conventions,
+        )
+        .await
+    }
+
+    /// Mounts a topology on `server` made of the given servers, tagged in order A, B, ...
+    async fn mount_topology(server: &MockServer, nodes: &[&MockServer]) {
+        let nodes = nodes
+            .iter()
+            .zip('A'..)
+            .map(|(node, tag)| {
+                serde_json::json!({
+                    "Url": node.uri(),
+                    "ClusterTag": tag.to_string(),
+                    "Database": "db",
+                    "ServerRole": "Member"
+                })
+            })
+            .collect::<Vec<_>>();
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "Nodes": nodes, "Etag": 1 })),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn new_waits_for_initial_topology() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "Nodes": [
+                    { "Url": server.uri(), "ClusterTag": "A", "Database": "db", "ServerRole": "Member" }
+                ],
+                "Etag": 1
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        // Act
+        let result = new_executor(&server).await;
+
+        // Assert
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn new_returns_an_error_for_every_failed_url() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/topology"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        // Act
+        let result = new_executor(&server).await;
+
+        // Assert
+        match result {
+            Err(RequestExecutorError::InitialTopologyUpdateFailed(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].0, Url::parse(&server.uri()).unwrap());
+            }
+            _ => panic!("expected the initial topology update to fail"),
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_runs_commands_defined_outside_the_library() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "BuildVersion": 54 })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let executor = RequestExecutor::new_for_single_node_without_configuration_updates(
+            Url::parse(&server.uri()).unwrap(),
+            "db".to_string(),
+            Default::default(),
+            DocumentConventions::default(),
+        )
+        .unwrap();
+
+        // Act
+        let result = executor.execute(GetBuildNumberCommand, None).await;
+
+        // Assert
+        assert_eq!(result.unwrap(), 54);
+    }
+
+    #[tokio::test]
+    async fn execute_fails_over_to_the_next_node() {
+        // Arrange
+        let a = MockServer::start().await;
+        let b = MockServer::start().await;
+        mount_topology(&a, &[&a, &b]).await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&a)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "BuildVersion": 54 })),
+            )
+            .expect(1)
+            .mount(&b)
+            .await;
+        let executor = new_executor(&a).await.unwrap();
+
+        // Act
+        let result = executor.execute(GetBuildNumberCommand, None).await;
+
+        // Assert
+        assert_eq!(result.unwrap(), 54);
+    }
+
+    #[tokio::test]
+    async fn execute_reports_every_node_when_all_are_down() {
+        // Arrange
+        let a = MockServer::start().await;
+        let b = MockServer::start().await;
+        mount_topology(&a, &[&a, &b]).await;
+        for server in [&a, &b] {
+            Mock::given(method("GET"))
+                .and(path("/build/version"))
+                .respond_with(ResponseTemplate::new(503))
+                .mount(server)
+                .await;
+        }
+        let executor = new_executor(&a).await.unwrap();
+
+        // Act
+        let result = executor.execute(GetBuildNumberCommand, None).await;
+
+        // Assert
+        match result {
+            Err(RequestExecutorError::RavenDbError(RavenDbError::AllTopologyNodesDown {
+                errors,
+            })) => assert_eq!(errors.len(), 2),
+            _ => panic!("expected all topology nodes to be down"),
+        }
+    }
+
+    #[tokio::test]
+    async fn execute_maps_server_errors_without_failing_over() {
+        // Arrange
+        let a = MockServer::start().await;
+        let b = MockServer::start().await;
+        mount_topology(&a, &[&a, &b]).await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(ResponseTemplate::new(409).set_body_json(serde_json::json!({
+                "Type": "Raven.Client.Exceptions.ConcurrencyException",
+                "Message": "Optimistic concurrency violation",
+                "Error": "Raven.Client.Exceptions.ConcurrencyException: ..."
+            })))
+            .expect(1)
+            .mount(&a)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&b)
+            .await;
+        let executor = new_executor(&a).await.unwrap();
+
+        // Act
+        let result = executor.execute(GetBuildNumberCommand, None).await;
+
+        // Assert
+        assert!(matches!(
+            result,
+            Err(RequestExecutorError::RavenDbError(
+                RavenDbError::ConcurrencyViolation { .. }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn execute_fails_over_when_a_node_times_out() {
+        // Arrange
+        let a = MockServer::start().await;
+        let b = MockServer::start().await;
+        mount_topology(&a, &[&a, &b]).await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&a)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({ "BuildVersion": 54 })),
+            )
+            .expect(1)
+            .mount(&b)
+            .await;
+        let conventions =
+            DocumentConventions::default().set_request_timeout(Duration::from_millis(200));
+        let executor = new_executor_with_conventions(&a, conventions)
+            .await
+            .unwrap();
+
+        // Act
+        let result = executor.execute(GetBuildNumberCommand, None).await;
+
+        // Assert
+        assert_eq!(result.unwrap(), 54);
+    }
+
+    #[tokio::test]
+    async fn dropping_execute_cancels_the_command() {
+        // Arrange
+        let a = MockServer::start().await;
+        let b = MockServer::start().await;
+        mount_topology(&a, &[&a, &b]).await;
+        // Node A fails slowly, so a command that kept running would go on to node B
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(ResponseTemplate::new(503).set_delay(Duration::from_millis(300)))
+            .mount(&a)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&b)
+            .await;
+        let executor = new_executor(&a).await.unwrap();
+
+        // Act
+        let result = tokio::time::timeout(
+            Duration::from_millis(50),
+            executor.execute(GetBuildNumberCommand, None),
+        )
+        .await;
+        tokio::time::sleep(Duration::from_millis(600)).await;
+
+        // Assert
+        assert!(result.is_err());
+        assert!(b.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn execute_reuses_cached_response_when_not_modified() {
+        // Arrange
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .and(header("If-None-Match", "\"A:1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/build/version"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"A:1\"")
+                    .set_body_json(serde_json::json!({ "BuildVersion": 54 })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let executor = RequestExecutor::new_for_single_node_without_configuration_updates(
+            Url::parse(&server.uri()).unwrap(),
+            "db".to_string(),
+            Default::default(),
+            DocumentConventions::default(),
+        )
+        .unwrap();
+
+        // Act
+        let first = executor.execute(GetBuildNumberCommand, None).await;
+        let second = executor.execute(GetBuildNumberCommand, None).await;
+        let stats = executor.http_cache_stats().await.unwrap();
+
+        // Assert
+        assert_eq!(first.unwrap(), 54);
+        assert_eq!(second.unwrap(), 54);
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn execute_presents_replaced_client_certificate() {
+        // Arrange
+        let (url, server_certificate_pem, presented) = start_tls_server().await;
+        let executor = RequestExecutor::new_for_single_node_without_configuration_updates(
+            url,
+            "db".to_string(),
+            HttpClientSettings {
+                trust_store: TrustStore::new(&[server_certificate_pem], &[] as &[&str]).unwrap(),
+                ..Default::default()
+            },
+            DocumentConventions::default(),
+        )
+        .unwrap();
+        let pem = std::fs::read("../ravendb-client_dev_cert.pem").unwrap();
+        let expected = rustls_pemfile::certs(&mut &pem[..]).unwrap().remove(0);
+
+        // Act
+        let replaced = executor.replace_client_certificate(Some(pem)).await;
+        let result = executor.execute(GetBuildNumberCommand, None).await;
+
+        // Assert
+        assert!(replaced.is_ok());
+        assert_eq!(result.unwrap(), 54);
+        assert_eq!(*presented.lock().unwrap(), vec![expected]);
+    }
+
+    #[tokio::test]
+    async fn replace_client_certificate_fails_for_invalid_pem() {
+        // Arrange
+        let server = MockServer::start().await;
+        let executor = RequestExecutor::new_for_single_node_without_configuration_updates(
+            Url::parse(&server.uri()).unwrap(),
+            "db".to_string(),
+            Default::default(),
+            DocumentConventions::default(),
+        )
+        .unwrap();
+
+        // Act
+        let result = executor
+            .replace_client_certificate(Some(b"not a certificate".to_vec()))
+            .await;
+
+        // Assert
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn execute_compresses_request_and_decompresses_response() {
+        // Arrange
+        let server = MockServer::start().await;
+        let body = br#"{"Commands":[]}"#.repeat(100);
+        Mock::given(method("POST"))
+            .and(path("/databases/db/bulk_docs"))
+            .and(header("Content-Encoding", "zstd"))
+            .and(header("Accept-Encoding", "zstd"))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .insert_header("Content-Encoding", "zstd")
+                    .set_body_bytes(zstd::encode_all(&br#"{"Results":[]}"#[..], 3).unwrap()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let executor = RequestExecutor::new_for_single_node_without_configuration_updates(
+            Url::parse(&server.uri()).unwrap(),
+            "db".to_string(),
+            Default::default(),
+            DocumentConventions::default()
+                .set_http_compression_algorithm(HttpCompressionAlgorithm::Zstd),
+        )
+        .unwrap();
+        let mut command = RawCommand::new(reqwest::Method::POST, "bulk_docs");
+        command.body = Some(body.clone());
+
+        // Act
+        let response = executor.execute(command, None).await.unwrap();
+
+        // Assert
+        assert_eq!(response.text().await.unwrap(), r#"{"Results":[]}"#);
+        let received = server.received_requests().await.unwrap();
+        assert_eq!(zstd::decode_all(received[0].body.as_slice()).unwrap(), body);
+    }
+}
@@ -0,0 +1,224 @@
+//! Persists topologies to disk, so a [`RequestExecutor`](super::RequestExecutor) can still start
+//! when none of its initial urls respond but nodes it learned about earlier do.
+//!
+//! Files are named after a hash of the initial urls, so stores pointed at different clusters can
+//! share one cache directory. Database names are hashed too, so they never escape the directory. Failing to read or write the cache is never fatal; it is logged and
+//! the executor carries on without it.
+
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+use sha1_smol::Sha1;
+use tracing::instrument;
+
+use crate::{
+    cluster_topology::ClusterTopology,
+    database_topology::{DatabaseTopology, GetDatabaseTopologyResult},
+};
+
+const DATABASE_TOPOLOGY_EXTENSION: &str = "raven-database-topology";
+const CLUSTER_TOPOLOGY_EXTENSION: &str = "raven-cluster-topology";
+
+/// Loads the cached topology of `database`, if there is one.
+#[instrument(level = "debug")]
+pub(crate) async fn try_load_database_topology(
+    cache_location: &Path,
+    initial_urls: &[Url],
+    database: &str,
+) -> Option<DatabaseTopology> {
+    let path = database_topology_path(cache_location, initial_urls, database);
+    let result = try_load::<GetDatabaseTopologyResult>(&path).await?;
+    Some(result.into())
+}
+
+/// Writes the topology of `database` to the cache, replacing any earlier version.
+#[instrument(level = "debug", skip(topology))]
+pub(crate) async fn try_save_database_topology(
+    cache_location: PathBuf,
+    initial_urls: Vec<Url>,
+    database: String,
+    topology: DatabaseTopology,
+) {
+    let path = database_topology_path(&cache_location, &initial_urls, &database);
+    try_save(&path, &GetDatabaseTopologyResult::from(&topology)).await;
+}
+
+/// Loads the cached cluster topology, if there is one.
+#[instrument(level = "debug")]
+pub(crate) async fn try_load_cluster_topology(
+    cache_location: &Path,
+    initial_urls: &[Url],
+) -> Option<ClusterTopology> {
+    let path = cluster_topology_path(cache_location, initial_urls);
+    try_load::<ClusterTopology>(&path).await
+}
+
+/// Writes the cluster topology to the cache, replacing any earlier version.
+#[instrument(level = "debug", skip(topology))]
+pub(crate) async fn try_save_cluster_topology(
+    cache_location: PathBuf,
+    initial_urls: Vec<Url>,
+    topology: ClusterTopology,
+) {
+    let path = cluster_topology_path(&cache_location, &initial_urls);
+    try_save(&path, &topology).await;
+}
+
+async fn try_load<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    let contents = match tokio::fs::read(path).await {
+        Ok(contents) => contents,
+        Err(e) => {
+            tracing::debug!("No cached topology at `{}`: {}", path.display(), e);
+            return None;
+        }
+    };
+
+    match serde_json::from_slice::<T>(&contents) {
+        Ok(topology) => {
+            tracing::info!("Loaded cached topology from `{}`", path.display());
+            Some(topology)
+        }
+        Err(e) => {
+            tracing::warn!(
+                "Ignoring unreadable cached topology at `{}`. Caused by: {}",
+                path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+async fn try_save<T: serde::Serialize>(path: &Path, topology: &T) {
+    let result = async {
+        let contents = serde_json::to_vec(topology)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Write to a temporary file first so a crash never leaves a half written cache behind.
+        // Each write gets its own, so concurrent saves don't write over each other's.
+        let mut temporary_name = path.file_name().unwrap_or_default().to_os_string();
+        temporary_name.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+        let temporary_path = path.with_file_name(temporary_name);
+        tokio::fs::write(&temporary_path, contents).await?;
+        if let Err(e) = tokio::fs::rename(&temporary_path, path).await {
+            let _ = tokio::fs::remove_file(&temporary_path).await;
+            return Err(e.into());
+        }
+        anyhow::Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::warn!(
+            "Unable to cache topology at `{}`. Caused by: {}",
+            path.display(),
+            e
+        );
+    }
+}
+
+fn database_topology_path(cache_location: &Path, initial_urls: &[Url], database: &str) -> PathBuf {
+    cache_location.join(format!(
+        "{}.{}.{}",
+        Sha1::from(database).digest(),
+        hash_urls(initial_urls),
+        DATABASE_TOPOLOGY_EXTENSION
+    ))
+}
+
+fn cluster_topology_path(cache_location: &Path, initial_urls: &[Url]) -> PathBuf {
+    cache_location.join(format!(
+        "{}.{}",
+        hash_urls(initial_urls),
+        CLUSTER_TOPOLOGY_EXTENSION
+    ))
+}
+
+/// Returns a hash identifying the cluster behind the urls, regardless of their order.
+fn hash_urls(urls: &[Url]) -> String {
+    let mut urls = urls.iter().map(Url::as_str).collect::<Vec<_>>();
+    urls.sort_unstable();
+
+    let mut hasher = Sha1::new();
+    for url in urls {
+        hasher.update(url.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher.digest().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use reqwest::Url;
+
+    use crate::{database_topology::DatabaseTopology, server_node::ServerNode};
+
+    use super::{
+        database_topology_path, hash_urls, try_load_database_topology, try_save_database_topology,
+    };
+
+    #[test]
+    fn hash_urls_ignores_url_order() {
+        let a = Url::parse("http://a.example.com").unwrap();
+        let b = Url::parse("http://b.example.com").unwrap();
+
+        assert_eq!(
+            hash_urls(&[a.clone(), b.clone()]),
+            hash_urls(&[b.clone(), a.clone()])
+        );
+        assert_ne!(hash_urls(&[a]), hash_urls(&[b]));
+    }
+
+    #[test]
+    fn database_topology_path_stays_in_cache_location() {
+        let cache_location = std::env::temp_dir().join("topologies");
+        let initial_urls = vec![Url::parse("http://a.example.com").unwrap()];
+
+        for database in ["../../etc/db", "a/b", "..", "/db"] {
+            let path = database_topology_path(&cache_location, &initial_urls, database);
+
+            assert_eq!(
+                path.parent(),
+                Some(cache_location.as_path()),
+                "{}",
+                database
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn saved_database_topology_can_be_loaded() {
+        // Arrange
+        let cache_location = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let initial_urls = vec![Url::parse("http://a.example.com").unwrap()];
+        let mut node = ServerNode::new(
+            Url::parse("http://b.example.com").unwrap(),
+            "db".to_string(),
+        );
+        node.cluster_tag = "B".to_string();
+        let topology = DatabaseTopology {
+            etag: 12,
+            nodes: HashSet::from([node.clone()]),
+            ..Default::default()
+        };
+
+        // Act
+        try_save_database_topology(
+            cache_location.clone(),
+            initial_urls.clone(),
+            "db".to_string(),
+            topology,
+        )
+        .await;
+        let result = try_load_database_topology(&cache_location, &initial_urls, "db").await;
+        let _ = std::fs::remove_dir_all(&cache_location);
+
+        // Assert
+        let result = result.unwrap();
+        assert_eq!(result.etag, 12);
+        assert_eq!(result.nodes, HashSet::from([node]));
+    }
+}
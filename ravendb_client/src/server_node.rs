@@ -1,12 +1,16 @@
 use reqwest::Url;
+use serde::{Deserialize, Serialize};
 
 use crate::cluster_topology::ClusterTopology;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, Hash, Serialize)]
+#[serde(rename_all = "PascalCase")]
 pub struct ServerNode {
     pub url: Url,
     pub database: String,
+    #[serde(default)]
     pub cluster_tag: String,
+    #[serde(default)]
     pub server_role: ServerRole,
 }
 impl ServerNode {
@@ -29,7 +33,7 @@ pub fn create_server_nodes_from_cluster_topology(topology: ClusterTopology) -> V
     todo!()
 }
 
-#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+#[derive(Debug, Default, Clone, Copy, Deserialize, Eq, PartialEq, Hash, Serialize)]
 pub enum ServerRole {
     #[default]
     None,